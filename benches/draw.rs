@@ -0,0 +1,77 @@
+//! Throughput of 10k triangle frames, drawn in batches of the former fixed draw buffer sizes
+//! and in a single draw call.
+//!
+//! Opens a window, so it needs a display: `cargo bench --bench draw`.
+
+use glow::HasContext;
+use gni::Limits;
+use gni_bin::{
+    render::{Backend, Render, Vertex},
+    Window,
+};
+use std::time::Instant;
+
+const TRIANGLES: usize = 10_000;
+const FRAMES: u32 = 100;
+
+/// Small triangles covering the screen in a 100x100 grid.
+fn triangle(i: usize) -> [Vertex; 3] {
+    let x = (i % 100) as f32 / 50. - 1.;
+    let y = (i / 100 % 100) as f32 / 50. - 1.;
+    let col = (i % 15 + 1) as u32;
+    let vertex = |dx, dy| Vertex {
+        pos: [x + dx, y + dy, 0.],
+        tex: [0., 0.],
+        col,
+    };
+
+    [vertex(0., 0.), vertex(0.02, 0.), vertex(0., 0.02)]
+}
+
+fn draw_frame(render: &mut Render, batch: Option<usize>) {
+    render.clear(0);
+    for i in 0..TRIANGLES {
+        render.add_to_buffer(triangle(i));
+        if batch.is_some_and(|n| (i + 1) % n == 0) {
+            render.draw_buffer();
+        }
+    }
+
+    render.draw_buffer();
+}
+
+fn main() {
+    let window = Window::new("gni bench");
+    let gl = unsafe {
+        glow::Context::from_loader_function(|s| window.context().get_proc_address(s).cast())
+    };
+
+    let mut render = Render::new(&window, Limits::default());
+    let modes = [
+        ("16 per draw call", Some(16)),
+        ("128 per draw call", Some(128)),
+        ("whole frame", None),
+    ];
+
+    for (name, batch) in modes {
+        // Warm up, so buffers have grown before measuring
+        draw_frame(&mut render, batch);
+        unsafe { gl.finish() };
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            draw_frame(&mut render, batch);
+            unsafe { gl.finish() };
+        }
+
+        let elapsed = start.elapsed();
+        let rate = (TRIANGLES as u32 * FRAMES) as f64 / elapsed.as_secs_f64();
+        println!(
+            "{:>18}: {:>10.2?} per frame, {:>12.0} triangles/s",
+            name,
+            elapsed / FRAMES,
+            rate,
+        );
+        render.check_error();
+    }
+}
@@ -0,0 +1,615 @@
+//! Compact binary encoding of the output protocol.
+//!
+//! A binary stream starts with [`MAGIC`] followed by commands. Every command is an opcode byte,
+//! the same as in the text protocol, and a fixed layout payload:
+//!
+//! | Command | Payload |
+//! |---------|---------|
+//! | `p` | index, r, g, b |
+//! | `a` | index, alpha |
+//! | `b` | blending mode |
+//! | `c` | index |
+//! | `t` | 3 points of little-endian `i16` x, y, z, then u, v, color index |
+//! | `v` | index, little-endian `u16` number of points, points as in `t` |
+//! | `e` | index, primitive, little-endian `u16` number of indices, little-endian `u16` indices |
+//! | `i` | index, sampling, width, height, texels packed two per byte, the first one in the high nibble |
+//! | `x` | index, sampling, palette mode, little-endian `u16` width and height, 256 colors of the own palette in the mode 1, packed texels in the mode 0 or one byte texels in the mode 1 |
+//! | `u` | index, little-endian `u16` x, y, width and height, one byte texels |
+//! | `r` | index of the image to remove |
+//! | `s` | index of the image to set |
+//! | `d` | depth mode |
+//! | `m` | 16 little-endian `i32` of the matrix, row by row |
+//! | `\n` | none, finishes the frame |
+
+use crate::{
+    output::{Command, CommandError},
+    Blend, Col, Depth, Elems, Img, Limits, Mat, Nib, ParseError, Patch, Pnt, Primitive, Sampling,
+    Tri, Verts,
+};
+use std::{
+    io::{self, Write},
+    iter::Peekable,
+};
+
+pub const MAGIC: [u8; 4] = *b"\0gnb";
+
+/// Checks whether the stream is binary and consumes the header if so.
+///
+/// Text streams never start with the zero byte, so only the first byte is peeked.
+pub fn read_header<B>(bytes: &mut Peekable<B>) -> Result<bool, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    if bytes.peek() != Some(&MAGIC[0]) {
+        return Ok(false);
+    }
+
+    for expected in MAGIC {
+        match ParseError::next(bytes)? {
+            next if next == expected => (),
+            next => return Err(ParseError::Unexpected(next)),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Writes the command in the binary encoding.
+pub fn write<W>(w: &mut W, command: &Command) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    fn point(buf: &mut Vec<u8>, p: &Pnt) {
+        for c in p.pos {
+            buf.extend(c.to_le_bytes());
+        }
+
+        buf.extend(p.tex);
+        buf.push(p.col.get());
+    }
+
+    let mut buf = Vec::new();
+    match command {
+        Command::Palette(idx, Col([r, g, b])) => buf.extend([b'p', idx.get(), *r, *g, *b]),
+        Command::Alpha(idx, alpha) => buf.extend([b'a', idx.get(), *alpha]),
+        Command::Blend(blend) => buf.extend([b'b', blend.get()]),
+        Command::Clear(idx) => buf.extend([b'c', idx.get()]),
+        Command::Triangle(Tri(points)) => {
+            buf.push(b't');
+            for p in points {
+                point(&mut buf, p);
+            }
+        }
+        Command::Vertices(idx, verts) => {
+            let points = verts.points();
+            buf.extend([b'v', *idx]);
+            buf.extend((points.len() as u16).to_le_bytes());
+            for p in points {
+                point(&mut buf, p);
+            }
+        }
+        Command::Elements(idx, elems) => {
+            buf.extend([b'e', *idx, elems.primitive().get()]);
+            buf.extend((elems.indices().len() as u16).to_le_bytes());
+            for idx in elems.indices() {
+                buf.extend(idx.to_le_bytes());
+            }
+        }
+        Command::Image(idx, img) => {
+            let (w, h) = img.size();
+            let sampling = img.sampling().get();
+            if img.is_compact() {
+                buf.extend([b'i', *idx, sampling, w as u8, h as u8]);
+            } else {
+                buf.extend([b'x', *idx, sampling, img.palette().is_some() as u8]);
+                buf.extend(w.to_le_bytes());
+                buf.extend(h.to_le_bytes());
+            }
+
+            match img.palette() {
+                Some(palette) => {
+                    buf.extend(palette.iter().flat_map(|col| col.0));
+                    buf.extend(img.data());
+                }
+                None => {
+                    for pair in img.data().chunks(2) {
+                        let hi = pair[0] << 4;
+                        let lo = pair.get(1).copied().unwrap_or(0);
+                        buf.push(hi | lo);
+                    }
+                }
+            }
+        }
+        Command::UpdateImage(idx, patch) => {
+            buf.extend([b'u', *idx]);
+            let (x, y) = patch.pos();
+            let (w, h) = patch.size();
+            for v in [x, y, w, h] {
+                buf.extend(v.to_le_bytes());
+            }
+
+            buf.extend(patch.data());
+        }
+        Command::RemoveImage(idx) => buf.extend([b'r', *idx]),
+        Command::SetImage(idx) => buf.extend([b's', *idx]),
+        Command::Depth(depth) => buf.extend([b'd', depth.get()]),
+        Command::Matrix(Mat(mat)) => {
+            buf.push(b'm');
+            for v in mat.iter().flatten() {
+                buf.extend(v.to_le_bytes());
+            }
+        }
+        Command::Finish => buf.push(b'\n'),
+    }
+
+    w.write_all(&buf)
+}
+
+/// Counts bytes read within a command.
+struct Counter<'a, B> {
+    bytes: &'a mut B,
+    col: usize,
+    end: bool,
+}
+
+impl<B> Iterator for Counter<'_, B>
+where
+    B: Iterator<Item = u8>,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.bytes.next() {
+            None => {
+                self.end = true;
+                None
+            }
+            Some(next) => {
+                self.col += 1;
+                Some(next)
+            }
+        }
+    }
+}
+
+/// Iterator over commands decoded from the binary encoding, without the header.
+///
+/// A binary stream can't be resynchronized, so the iterator stops after the first error.
+/// The error line is the command number and the column is the byte offset within the command.
+pub struct Commands<B> {
+    bytes: B,
+    n: usize,
+    failed: bool,
+    max_memory: usize,
+}
+
+impl<B> Commands<B> {
+    pub fn new(bytes: B) -> Self {
+        Self {
+            bytes,
+            n: 0,
+            failed: false,
+            max_memory: usize::MAX,
+        }
+    }
+
+    /// Rejects images exceeding the memory limit before their texels are read.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_memory = limits.memory;
+        self
+    }
+
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+}
+
+impl<B> Iterator for Commands<B>
+where
+    B: Iterator<Item = u8>,
+{
+    type Item = Result<Command, CommandError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let next = self.bytes.next()?;
+        self.n += 1;
+
+        let mut counter = Counter {
+            bytes: &mut self.bytes,
+            col: 1,
+            end: false,
+        };
+
+        match read_command(next, &mut counter, self.max_memory) {
+            Ok(command) => Some(Ok(command)),
+            Err(error) => {
+                self.failed = true;
+                Some(Err(CommandError {
+                    line: self.n,
+                    col: if counter.end {
+                        counter.col + 1
+                    } else {
+                        counter.col
+                    },
+                    error,
+                }))
+            }
+        }
+    }
+}
+
+fn read_nib<B>(bytes: &mut B) -> Result<Nib, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let next = ParseError::next(bytes)?;
+    Nib::new(next).ok_or(ParseError::Nibble(next))
+}
+
+fn read_i16<B>(bytes: &mut B) -> Result<i16, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let lo = ParseError::next(bytes)?;
+    let hi = ParseError::next(bytes)?;
+    Ok(i16::from_le_bytes([lo, hi]))
+}
+
+fn read_pnt<B>(bytes: &mut B) -> Result<Pnt, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let x = read_i16(bytes).map_err(|err| err.within("Pnt.pos.x"))?;
+    let y = read_i16(bytes).map_err(|err| err.within("Pnt.pos.y"))?;
+    let z = read_i16(bytes).map_err(|err| err.within("Pnt.pos.z"))?;
+    let u = ParseError::next(bytes).map_err(|err| err.within("Pnt.tex.u"))?;
+    let v = ParseError::next(bytes).map_err(|err| err.within("Pnt.tex.v"))?;
+    let col = read_nib(bytes).map_err(|err| err.within("Pnt.col"))?;
+
+    Ok(Pnt {
+        pos: [x, y, z],
+        tex: [u, v],
+        col,
+    })
+}
+
+fn read_u16<B>(bytes: &mut B) -> Result<u16, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let lo = ParseError::next(bytes)?;
+    let hi = ParseError::next(bytes)?;
+    Ok(u16::from_le_bytes([lo, hi]))
+}
+
+fn read_img<B>(bytes: &mut B, extended: bool, max_memory: usize) -> Result<Img, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let (indexed, w, h) = if extended {
+        let mode = ParseError::next(bytes).map_err(|err| err.within("Img.mode"))?;
+        let indexed = match mode {
+            0 => false,
+            1 => true,
+            _ => return Err(ParseError::Unexpected(mode).within("Img.mode")),
+        };
+
+        let w = read_u16(bytes).map_err(|err| err.within("Img.w"))?;
+        let h = read_u16(bytes).map_err(|err| err.within("Img.h"))?;
+        (indexed, w, h)
+    } else {
+        let w = ParseError::next(bytes).map_err(|err| err.within("Img.w"))?;
+        let h = ParseError::next(bytes).map_err(|err| err.within("Img.h"))?;
+        (false, w as u16, h as u16)
+    };
+
+    Img::check_memory((w, h), indexed, max_memory)?;
+
+    let len = w as usize * h as usize;
+    if indexed {
+        let mut palette = Box::new([Col::new(0, 0, 0); 256]);
+        for col in palette.iter_mut() {
+            for c in &mut col.0 {
+                *c = ParseError::next(bytes).map_err(|err| err.within("Img.palette"))?;
+            }
+        }
+
+        let mut data = Vec::with_capacity(len.min(0x10000));
+        for _ in 0..len {
+            data.push(ParseError::next(bytes).map_err(|err| err.within("Img.data"))?);
+        }
+
+        return Ok(Img::indexed(data, (w, h), palette).unwrap());
+    }
+
+    let mut data = Vec::with_capacity(len.min(0x10000));
+    while data.len() < len {
+        let pair = ParseError::next(bytes).map_err(|err| err.within("Img.data"))?;
+        data.push(Nib::new(pair >> 4).unwrap());
+        if data.len() < len {
+            data.push(Nib::new(pair & 0x0F).unwrap());
+        }
+    }
+
+    Ok(Img::extended(data, (w, h)).unwrap())
+}
+
+fn read_patch<B>(bytes: &mut B) -> Result<Patch, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let x = read_u16(bytes).map_err(|err| err.within("Patch.x"))?;
+    let y = read_u16(bytes).map_err(|err| err.within("Patch.y"))?;
+    let w = read_u16(bytes).map_err(|err| err.within("Patch.w"))?;
+    let h = read_u16(bytes).map_err(|err| err.within("Patch.h"))?;
+    let len = w as usize * h as usize;
+    let mut data = Vec::with_capacity(len.min(0x10000));
+    for _ in 0..len {
+        data.push(ParseError::next(bytes).map_err(|err| err.within("Patch.data"))?);
+    }
+
+    Ok(Patch::new(data, (x, y), (w, h)).unwrap())
+}
+
+fn read_command<B>(next: u8, bytes: &mut B, max_memory: usize) -> Result<Command, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let command = match next {
+        b'p' => {
+            let idx = read_nib(bytes).map_err(|err| err.within("palette.idx"))?;
+            let r = ParseError::next(bytes).map_err(|err| err.within("Col.r"))?;
+            let g = ParseError::next(bytes).map_err(|err| err.within("Col.g"))?;
+            let b = ParseError::next(bytes).map_err(|err| err.within("Col.b"))?;
+            Command::Palette(idx, Col::new(r, g, b))
+        }
+        b'a' => {
+            let idx = read_nib(bytes).map_err(|err| err.within("alpha.idx"))?;
+            let alpha = ParseError::next(bytes).map_err(|err| err.within("alpha.value"))?;
+            Command::Alpha(idx, alpha)
+        }
+        b'b' => {
+            let mode = ParseError::next(bytes).map_err(|err| err.within("blend.mode"))?;
+            let blend = Blend::new(mode).ok_or(ParseError::Unexpected(mode))?;
+            Command::Blend(blend)
+        }
+        b'c' => {
+            let idx = read_nib(bytes).map_err(|err| err.within("clear.idx"))?;
+            Command::Clear(idx)
+        }
+        b't' => {
+            let a = read_pnt(bytes)?;
+            let b = read_pnt(bytes)?;
+            let c = read_pnt(bytes)?;
+            Command::Triangle(Tri([a, b, c]))
+        }
+        b'v' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("vertices.idx"))?;
+            let len = read_u16(bytes).map_err(|err| err.within("Verts.len"))?;
+            let mut points = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                points.push(read_pnt(bytes)?);
+            }
+
+            Command::Vertices(idx, Verts::new(points).unwrap())
+        }
+        b'e' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("elements.idx"))?;
+            let mode = ParseError::next(bytes).map_err(|err| err.within("Elems.primitive"))?;
+            let primitive = Primitive::new(mode)
+                .ok_or_else(|| ParseError::Unexpected(mode).within("Elems.primitive"))?;
+            let len = read_u16(bytes).map_err(|err| err.within("Elems.len"))?;
+            let mut indices = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                indices.push(read_u16(bytes).map_err(|err| err.within("Elems.indices"))?);
+            }
+
+            Command::Elements(idx, Elems::new(primitive, indices).unwrap())
+        }
+        b'i' | b'x' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("image.idx"))?;
+            if idx == 0 {
+                return Err(ParseError::ZeroIndex);
+            }
+
+            let mode = ParseError::next(bytes).map_err(|err| err.within("image.sampling"))?;
+            let sampling = Sampling::new(mode).ok_or(ParseError::Unexpected(mode))?;
+            let img = read_img(bytes, next == b'x', max_memory)?;
+            Command::Image(idx, img.with_sampling(sampling))
+        }
+        b'u' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("update_image.idx"))?;
+            let patch = read_patch(bytes)?;
+            Command::UpdateImage(idx, patch)
+        }
+        b'r' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("remove_image.idx"))?;
+            Command::RemoveImage(idx)
+        }
+        b's' => {
+            let idx = ParseError::next(bytes).map_err(|err| err.within("set_image.idx"))?;
+            Command::SetImage(idx)
+        }
+        b'd' => {
+            let mode = ParseError::next(bytes).map_err(|err| err.within("depth.mode"))?;
+            let depth = Depth::new(mode).ok_or(ParseError::Unexpected(mode))?;
+            Command::Depth(depth)
+        }
+        b'm' => {
+            let mut mat = [[0; 4]; 4];
+            for v in mat.iter_mut().flatten() {
+                let mut le = [0; 4];
+                for b in &mut le {
+                    *b = ParseError::next(bytes).map_err(|err| err.within("Mat"))?;
+                }
+
+                *v = i32::from_le_bytes(le);
+            }
+
+            Command::Matrix(Mat(mat))
+        }
+        b'\n' => Command::Finish,
+        _ => return Err(ParseError::Unexpected(next)),
+    };
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands() -> Vec<Command> {
+        let nib = |n| Nib::new(n).unwrap();
+        let point = |x, y, col| Pnt {
+            pos: [x, y, -0x10],
+            tex: [0x00, 0xFF],
+            col: nib(col),
+        };
+
+        vec![
+            Command::Palette(nib(0xF), Col::new(0xFF, 0x80, 0x01)),
+            Command::Alpha(nib(0x0), 0x00),
+            Command::Blend(Blend::Add),
+            Command::Clear(nib(0xF)),
+            Command::Image(
+                0x01,
+                Img::new([nib(0x1), nib(0xA), nib(0xF)], (3, 1)).unwrap(),
+            ),
+            Command::Image(0xFF, Img::new([], (0, 0)).unwrap()),
+            Command::Image(
+                0x02,
+                Img::new([nib(0x3)], (1, 1))
+                    .unwrap()
+                    .with_sampling(Sampling {
+                        linear: false,
+                        clamp: true,
+                        transparent: false,
+                    }),
+            ),
+            Command::Image(0x03, Img::extended([nib(0x4); 0x101], (0x101, 1)).unwrap()),
+            Command::Image(
+                0x04,
+                Img::indexed(
+                    [0x00, 0xFF, 0x80],
+                    (3, 1),
+                    Box::new([Col::new(1, 2, 3); 256]),
+                )
+                .unwrap(),
+            ),
+            Command::UpdateImage(0x04, Patch::new([0x01, 0xFE], (1, 0), (2, 1)).unwrap()),
+            Command::Vertices(
+                0x01,
+                Verts::new([point(-0x100, 0x100, 0x1), point(0x7FFF, -0x8000, 0xF)]).unwrap(),
+            ),
+            Command::Elements(
+                0x01,
+                Elems::new(Primitive::List, [0x0001, 0x0100, 0xFFFF]).unwrap(),
+            ),
+            Command::SetImage(0x01),
+            Command::RemoveImage(0xFF),
+            Command::Depth(Depth::Greater),
+            Command::Matrix(Mat::from_f32([
+                [1., 0., 0., -0.5],
+                [0., 1.5, 0., 0.],
+                [0., 0., -1., -0.25],
+                [0., 0., -1., 0.],
+            ])),
+            Command::Triangle(Tri([
+                point(-0x100, -0x100, 0x1),
+                point(0x100, -0x100, 0x2),
+                point(0x0, 0x7FFF, 0x3),
+            ])),
+            Command::Finish,
+        ]
+    }
+
+    #[test]
+    fn write() {
+        let nib = Nib::new(0x3).unwrap();
+        let mut actual = Vec::new();
+        super::write(&mut actual, &Command::Palette(nib, Col::new(1, 2, 3))).unwrap();
+        super::write(&mut actual, &Command::Finish).unwrap();
+        let img = Img::new([nib; 3], (1, 3)).unwrap();
+        super::write(&mut actual, &Command::Image(0x10, img)).unwrap();
+
+        let expected = b"p\x03\x01\x02\x03\ni\x10\x00\x01\x03\x33\x30";
+        assert_eq!(actual, expected);
+
+        let mut actual = Vec::new();
+        let img = Img::indexed([0xAB], (1, 1), Box::new([Col::new(1, 2, 3); 256])).unwrap();
+        super::write(&mut actual, &Command::Image(0x10, img)).unwrap();
+
+        let mut expected = b"x\x10\x00\x01\x01\x00\x01\x00".to_vec();
+        expected.extend([1, 2, 3].repeat(256));
+        expected.push(0xAB);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn round_trip() {
+        let commands = commands();
+        let mut stream = MAGIC.to_vec();
+        for command in &commands {
+            super::write(&mut stream, command).unwrap();
+        }
+
+        let mut bytes = stream.into_iter().peekable();
+        assert_eq!(read_header(&mut bytes), Ok(true));
+
+        let actual: Result<Vec<_>, _> = Commands::new(bytes).collect();
+        assert_eq!(actual, Ok(commands));
+    }
+
+    #[test]
+    fn header() {
+        let mut bytes = b"cf\n".iter().copied().peekable();
+        assert_eq!(read_header(&mut bytes), Ok(false));
+        assert_eq!(bytes.next(), Some(b'c'));
+
+        let mut bytes = b"\0gnt".iter().copied().peekable();
+        assert_eq!(read_header(&mut bytes), Err(ParseError::Unexpected(b't')));
+    }
+
+    #[test]
+    fn errors() {
+        let actual: Vec<_> = Commands::new(b"c\x01p\x10".iter().copied()).collect();
+        let expected = [
+            Ok(Command::Clear(Nib::new(0x1).unwrap())),
+            Err(CommandError {
+                line: 2,
+                col: 2,
+                error: ParseError::Nibble(0x10).within("palette.idx"),
+            }),
+        ];
+        assert_eq!(actual, expected);
+
+        let actual: Vec<_> = Commands::new(b"t\x00".iter().copied()).collect();
+        let expected = [Err(CommandError {
+            line: 1,
+            col: 3,
+            error: ParseError::End.within("Pnt.pos.x"),
+        })];
+        assert_eq!(actual, expected);
+
+        // The size is checked before the palette is read
+        let limits = Limits {
+            images: 1,
+            memory: 0x300,
+            ..Limits::default()
+        };
+        let actual: Vec<_> = Commands::new(b"x\x01\x00\x01\x01\x00\x01\x00".iter().copied())
+            .with_limits(limits)
+            .collect();
+        let expected = [Err(CommandError {
+            line: 1,
+            col: 8,
+            error: ParseError::Limit(0x301),
+        })];
+        assert_eq!(actual, expected);
+    }
+}
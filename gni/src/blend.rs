@@ -0,0 +1,66 @@
+use crate::{hex, Parse, ParseError};
+
+/// Blending of drawn colors with the framebuffer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Blend {
+    /// Colors replace the framebuffer.
+    #[default]
+    Off,
+    /// Colors are mixed with the framebuffer by their alpha.
+    Alpha,
+    /// Colors multiplied by their alpha are added to the framebuffer.
+    Add,
+}
+
+impl Blend {
+    const MODES: [Self; 3] = [Self::Off, Self::Alpha, Self::Add];
+
+    pub fn new(mode: u8) -> Option<Self> {
+        Self::MODES.get(mode as usize).copied()
+    }
+
+    pub fn get(self) -> u8 {
+        self as u8
+    }
+}
+
+impl<B> Parse<B> for Blend
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let byte = ParseError::next(bytes)?;
+        let mode = hex::read_u4(byte)?;
+        Self::new(mode).ok_or(ParseError::Unexpected(byte))
+    }
+}
+
+impl std::fmt::Display for Blend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u4(self.get(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let actual = Blend::from_bytes(*b"1");
+        let expected = Ok(Blend::Alpha);
+        assert_eq!(actual, expected);
+
+        let actual = Blend::from_bytes(*b"3");
+        let expected = Err(ParseError::Unexpected(b'3'));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        for mode in Blend::MODES {
+            let actual = Blend::from_bytes(mode.to_string().into_bytes());
+            assert_eq!(actual, Ok(mode));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Parse, ParseError};
+use crate::{hex, Parse, ParseError};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Col(pub [u8; 3]);
@@ -14,13 +14,23 @@ where
     B: Iterator<Item = u8>,
 {
     fn parse(bytes: &mut B) -> Result<Self, ParseError> {
-        let r = u8::parse(bytes)?;
-        let g = u8::parse(bytes)?;
-        let b = u8::parse(bytes)?;
+        let r = u8::parse(bytes).map_err(|err| err.within("Col.r"))?;
+        let g = u8::parse(bytes).map_err(|err| err.within("Col.g"))?;
+        let b = u8::parse(bytes).map_err(|err| err.within("Col.b"))?;
         Ok(Self::new(r, g, b))
     }
 }
 
+impl std::fmt::Display for Col {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for c in self.0 {
+            hex::write_u8(c, f)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +41,11 @@ mod tests {
         let expected = Ok(Col::new(0x00, 0x11, 0x22));
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn display() {
+        let actual = Col::new(0x00, 0x11, 0xAB).to_string();
+        let expected = "0011ab";
+        assert_eq!(actual, expected);
+    }
 }
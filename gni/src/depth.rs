@@ -0,0 +1,79 @@
+use crate::{hex, Parse, ParseError};
+
+/// Depth test mode, the comparison passes when the new depth relates to the stored one.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Depth {
+    #[default]
+    Off,
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl Depth {
+    const MODES: [Self; 9] = [
+        Self::Off,
+        Self::Never,
+        Self::Less,
+        Self::Equal,
+        Self::LessEqual,
+        Self::Greater,
+        Self::NotEqual,
+        Self::GreaterEqual,
+        Self::Always,
+    ];
+
+    pub fn new(mode: u8) -> Option<Self> {
+        Self::MODES.get(mode as usize).copied()
+    }
+
+    pub fn get(self) -> u8 {
+        self as u8
+    }
+}
+
+impl<B> Parse<B> for Depth
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let byte = ParseError::next(bytes)?;
+        let mode = hex::read_u4(byte)?;
+        Self::new(mode).ok_or(ParseError::Unexpected(byte))
+    }
+}
+
+impl std::fmt::Display for Depth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u4(self.get(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let actual = Depth::from_bytes(*b"4");
+        let expected = Ok(Depth::LessEqual);
+        assert_eq!(actual, expected);
+
+        let actual = Depth::from_bytes(*b"9");
+        let expected = Err(ParseError::Unexpected(b'9'));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        for mode in Depth::MODES {
+            let actual = Depth::from_bytes(mode.to_string().into_bytes());
+            assert_eq!(actual, Ok(mode));
+        }
+    }
+}
@@ -10,6 +10,31 @@ pub(crate) fn read_u8([a, b]: [u8; 2]) -> Result<u8, u8> {
     Ok(read_u4(a)? << 4 | read_u4(b)?)
 }
 
+pub(crate) fn write_u4(n: u8, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let c = match n {
+        0..=9 => b'0' + n,
+        10..=15 => b'a' + n - 10,
+        _ => unreachable!(),
+    };
+
+    write!(f, "{}", c as char)
+}
+
+pub(crate) fn write_u8(b: u8, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write_u4(b >> 4 & 0x0F, f)?;
+    write_u4(b & 0x0F, f)
+}
+
+pub(crate) fn write_u16(n: u16, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write_u8((n >> 8 & 0xFF) as u8, f)?;
+    write_u8((n & 0xFF) as u8, f)
+}
+
+pub(crate) fn write_u32(n: u32, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write_u16((n >> 16 & 0xFFFF) as u16, f)?;
+    write_u16((n & 0xFFFF) as u16, f)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +61,22 @@ mod tests {
         let expected = Err(b'q');
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn write() {
+        struct Wrap(u16);
+
+        impl std::fmt::Display for Wrap {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write_u16(self.0, f)
+            }
+        }
+
+        for n in 0..=u16::MAX {
+            let s = Wrap(n).to_string();
+            let [a, b, c, d] = [0, 1, 2, 3].map(|i| s.as_bytes()[i]);
+            let actual = (read_u8([a, b]).unwrap() as u16) << 8 | read_u8([c, d]).unwrap() as u16;
+            assert_eq!(actual, n);
+        }
+    }
 }
@@ -1,47 +1,239 @@
-use crate::{Nib, Parse, ParseError};
+use crate::{hex, Col, Nib, Parse, ParseError, Patch, Sampling};
 
-#[derive(Debug, Eq, PartialEq)]
+/// Image of palette indices.
+///
+/// Texels index the global 16 color palette, or the own 256 color palette of an indexed image.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Img {
-    data: Box<[Nib]>,
-    size: (u8, u8),
+    data: Box<[u8]>,
+    size: (u16, u16),
+    palette: Option<Box<[Col; 256]>>,
+    sampling: Sampling,
 }
 
 impl Img {
-    pub fn new<D>(data: D, size: (u8, u8)) -> Option<Self>
+    /// Creates an image of the global palette indices.
+    pub fn new<D>(data: D, (w, h): (u8, u8)) -> Option<Self>
     where
         D: Into<Box<[Nib]>>,
     {
-        let data = data.into();
+        Self::extended(data, (w as u16, h as u16))
+    }
+
+    /// Creates an image of the global palette indices with a `u16` size.
+    pub fn extended<D>(data: D, size: (u16, u16)) -> Option<Self>
+    where
+        D: Into<Box<[Nib]>>,
+    {
+        let data: Box<[Nib]> = data.into();
+        let data = data.iter().map(|nib| nib.get()).collect();
+        Self::with_data(data, size, None)
+    }
+
+    /// Creates an image with its own palette.
+    pub fn indexed<D>(data: D, size: (u16, u16), palette: Box<[Col; 256]>) -> Option<Self>
+    where
+        D: Into<Box<[u8]>>,
+    {
+        Self::with_data(data.into(), size, Some(palette))
+    }
+
+    fn with_data(
+        data: Box<[u8]>,
+        size: (u16, u16),
+        palette: Option<Box<[Col; 256]>>,
+    ) -> Option<Self> {
         if data.len() != size.0 as usize * size.1 as usize {
             return None;
         }
 
-        Some(Self { data, size })
+        Some(Self {
+            data,
+            size,
+            palette,
+            sampling: Sampling::default(),
+        })
+    }
+
+    pub fn with_sampling(self, sampling: Sampling) -> Self {
+        Self { sampling, ..self }
     }
 
-    pub fn data(&self) -> &[Nib] {
+    /// Texel indices, row by row. They index the own palette if there is one.
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
-    pub fn size(&self) -> (u8, u8) {
+    pub fn size(&self) -> (u16, u16) {
         self.size
     }
+
+    pub fn palette(&self) -> Option<&[Col; 256]> {
+        self.palette.as_deref()
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    /// Memory of texels and the own palette in bytes.
+    pub fn memory(&self) -> usize {
+        let palette = self.palette.as_ref().map_or(0, |palette| palette.len() * 3);
+        self.data.len() + palette
+    }
+
+    /// Copies the patch texels into the image.
+    /// Returns `false` and leaves the image as is if the patch doesn't [fit](Patch::fits) it.
+    pub fn update(&mut self, patch: &Patch) -> bool {
+        if !patch.fits(self.size, self.palette.is_some()) {
+            return false;
+        }
+
+        let (x, y) = patch.pos();
+        let (w, h) = patch.size();
+        let width = self.size.0 as usize;
+        for row in 0..h as usize {
+            let start = (y as usize + row) * width + x as usize;
+            let src = &patch.data()[row * w as usize..][..w as usize];
+            self.data[start..start + w as usize].copy_from_slice(src);
+        }
+
+        true
+    }
+
+    /// Checks whether the image fits the compact `i` command,
+    /// otherwise the extended `x` one is needed.
+    pub fn is_compact(&self) -> bool {
+        let (w, h) = self.size;
+        self.palette.is_none() && w <= u8::MAX as u16 && h <= u8::MAX as u16
+    }
+
+    /// Memory of an image of the size, as counted by [`Img::memory`].
+    /// Fails with [`ParseError::Limit`] if it's larger than `max_memory`,
+    /// so the size is checked before texels are read.
+    pub(crate) fn check_memory(
+        (w, h): (u16, u16),
+        indexed: bool,
+        max_memory: usize,
+    ) -> Result<(), ParseError> {
+        let palette = if indexed { 256 * 3 } else { 0 };
+        let memory = w as usize * h as usize + palette;
+        if memory > max_memory {
+            return Err(ParseError::Limit(memory));
+        }
+
+        Ok(())
+    }
+
+    /// Parses the compact form, failing if the image takes more than `max_memory`.
+    pub(crate) fn parse_compact<B>(bytes: &mut B, max_memory: usize) -> Result<Self, ParseError>
+    where
+        B: Iterator<Item = u8>,
+    {
+        let w = u8::parse(bytes).map_err(|err| err.within("Img.w"))?;
+        let h = u8::parse(bytes).map_err(|err| err.within("Img.h"))?;
+        Self::check_memory((w as u16, h as u16), false, max_memory)?;
+        let len = w as usize * h as usize;
+        let mut data = Vec::with_capacity(len);
+        for _ in 0..len {
+            let col = Nib::parse(bytes).map_err(|err| err.within("Img.data"))?;
+            data.push(col);
+        }
+        Ok(Self::new(data, (w, h)).unwrap())
+    }
+
+    /// Parses the extended form: the palette mode, `u16` size,
+    /// the own palette in the mode 1 and texels of one or two hex digits.
+    /// Fails if the image takes more than `max_memory`.
+    pub(crate) fn parse_extended<B>(bytes: &mut B, max_memory: usize) -> Result<Self, ParseError>
+    where
+        B: Iterator<Item = u8>,
+    {
+        let byte = ParseError::next(bytes).map_err(|err| err.within("Img.mode"))?;
+        let mode = hex::read_u4(byte).map_err(|b| ParseError::from(b).within("Img.mode"))?;
+        let indexed = match mode {
+            0 => false,
+            1 => true,
+            _ => return Err(ParseError::Unexpected(byte).within("Img.mode")),
+        };
+
+        let w = u16::parse(bytes).map_err(|err| err.within("Img.w"))?;
+        let h = u16::parse(bytes).map_err(|err| err.within("Img.h"))?;
+        Self::check_memory((w, h), indexed, max_memory)?;
+        let palette = if indexed {
+            let mut palette = Box::new([Col::new(0, 0, 0); 256]);
+            for col in palette.iter_mut() {
+                *col = Col::parse(bytes).map_err(|err| err.within("Img.palette"))?;
+            }
+
+            Some(palette)
+        } else {
+            None
+        };
+
+        // The size isn't trusted before the data is actually read
+        let len = w as usize * h as usize;
+        let mut data = Vec::with_capacity(len.min(0x10000));
+        for _ in 0..len {
+            let idx = if indexed {
+                u8::parse(bytes)
+            } else {
+                Nib::parse(bytes).map(Nib::get)
+            };
+
+            data.push(idx.map_err(|err| err.within("Img.data"))?);
+        }
+
+        Ok(Self::with_data(data.into(), (w, h), palette).unwrap())
+    }
 }
 
+/// Parses the compact form of `u8` size and one hex digit texels.
 impl<B> Parse<B> for Img
 where
     B: Iterator<Item = u8>,
 {
     fn parse(bytes: &mut B) -> Result<Self, ParseError> {
-        let w = u8::parse(bytes)?;
-        let h = u8::parse(bytes)?;
-        let len = w as usize * h as usize;
-        let mut data = Vec::with_capacity(len);
-        for _ in 0..len {
-            let col = Nib::parse(bytes)?;
-            data.push(col);
+        Self::parse_compact(bytes, usize::MAX)
+    }
+}
+
+/// Displays the size and texels, in the compact form if the image [fits](Img::is_compact) it.
+/// The sampling is written by the image command, since it's an optional trailing digit.
+impl std::fmt::Display for Img {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (w, h) = self.size;
+        if self.is_compact() {
+            hex::write_u8(w as u8, f)?;
+            hex::write_u8(h as u8, f)?;
+            for &idx in self.data.iter() {
+                hex::write_u4(idx, f)?;
+            }
+
+            return Ok(());
         }
-        Ok(Self::new(data, (w, h)).unwrap())
+
+        write!(f, "{}", self.palette.is_some() as u8)?;
+        hex::write_u16(w, f)?;
+        hex::write_u16(h, f)?;
+        match &self.palette {
+            Some(palette) => {
+                for col in palette.iter() {
+                    write!(f, "{}", col)?;
+                }
+
+                for &idx in self.data.iter() {
+                    hex::write_u8(idx, f)?;
+                }
+            }
+            None => {
+                for &idx in self.data.iter() {
+                    hex::write_u4(idx, f)?;
+                }
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -84,4 +276,69 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn parse_extended() {
+        let stream = format!("001000001{}", "0".repeat(0x100));
+        let actual = Img::parse_extended(&mut stream.bytes(), usize::MAX);
+        let expected = Ok(Img::extended([Nib::new(0x0).unwrap(); 0x100], (0x100, 1)).unwrap());
+        assert_eq!(actual, expected);
+
+        let mut stream = String::from("100020001");
+        for i in 0..=255 {
+            stream += &format!("{:02x}0000", i);
+        }
+
+        stream += "ff80";
+        let actual = Img::parse_extended(&mut stream.bytes(), usize::MAX);
+        let mut palette = Box::new([Col::new(0, 0, 0); 256]);
+        for (i, col) in palette.iter_mut().enumerate() {
+            *col = Col::new(i as u8, 0, 0);
+        }
+
+        let expected = Ok(Img::indexed([0xFF, 0x80], (2, 1), palette).unwrap());
+        assert_eq!(actual, expected);
+
+        let actual = Img::parse_extended(&mut b"2".iter().copied(), usize::MAX);
+        let expected = Err(ParseError::Unexpected(b'2').within("Img.mode"));
+        assert_eq!(actual, expected);
+
+        // The size is checked before the palette and texels
+        let actual = Img::parse_extended(&mut b"101000100".iter().copied(), 0x10000 + 767);
+        let expected = Err(ParseError::Limit(0x10000 + 768));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn update() {
+        let nib = |n| Nib::new(n).unwrap();
+        let mut img = Img::new([nib(0x0); 6], (3, 2)).unwrap();
+        let patch = Patch::new([0x1, 0x2, 0x3, 0x4], (1, 0), (2, 2)).unwrap();
+        assert!(img.update(&patch));
+
+        let expected = Img::new([0x0, 0x1, 0x2, 0x0, 0x3, 0x4].map(nib), (3, 2)).unwrap();
+        assert_eq!(img, expected);
+
+        let patch = Patch::new([0x1, 0x2], (2, 0), (2, 1)).unwrap();
+        assert!(!img.update(&patch));
+        assert_eq!(img, expected);
+    }
+
+    #[test]
+    fn display() {
+        let data = [0x0, 0x1, 0xE, 0xF].map(|n| Nib::new(n).unwrap());
+        let actual = Img::new(data, (2, 2)).unwrap().to_string();
+        let expected = "020201ef";
+        assert_eq!(actual, expected);
+
+        let data = [Nib::new(0xA).unwrap(); 0x100];
+        let actual = Img::extended(data, (1, 0x100)).unwrap().to_string();
+        let expected = format!("000010100{}", "a".repeat(0x100));
+        assert_eq!(actual, expected);
+
+        let palette = Box::new([Col::new(0x01, 0x02, 0x03); 256]);
+        let actual = Img::indexed([0xAB], (1, 1), palette).unwrap().to_string();
+        let expected = format!("100010001{}ab", "010203".repeat(256));
+        assert_eq!(actual, expected);
+    }
 }
@@ -1,18 +1,4 @@
-fn print_byte(b: u8, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    for n in [b >> 4 & 0x0F, b & 0x0F] {
-        write!(
-            f,
-            "{}",
-            match n {
-                0..=9 => b'0' + n,
-                10..=15 => b'a' + n - 10,
-                _ => unreachable!(),
-            } as char
-        )?
-    }
-
-    Ok(())
-}
+use crate::{hex, Parse, ParseError};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Direction {
@@ -22,6 +8,21 @@ pub enum Direction {
     Down,
 }
 
+impl<B> Parse<B> for Direction
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        match ParseError::next(bytes)? {
+            b'l' => Ok(Direction::Left),
+            b'r' => Ok(Direction::Right),
+            b'u' => Ok(Direction::Up),
+            b'd' => Ok(Direction::Down),
+            next => Err(ParseError::Unexpected(next)),
+        }
+    }
+}
+
 impl std::fmt::Display for Direction {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
@@ -59,7 +60,7 @@ impl std::fmt::Display for Action {
 
         impl std::fmt::Display for Wrap {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                print_byte(self.0, f)
+                hex::write_u8(self.0, f)
             }
         }
 
@@ -87,16 +88,73 @@ impl std::fmt::Display for Action {
     }
 }
 
+impl<B> Parse<B> for Action
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        match ParseError::next(bytes)? {
+            b'a' => (),
+            next => return Err(ParseError::Unexpected(next)),
+        }
+
+        let action = match ParseError::next(bytes)? {
+            b'l' => {
+                let x = u8::parse(bytes).map_err(|err| err.within("Action.x"))? as i8;
+                let y = u8::parse(bytes).map_err(|err| err.within("Action.y"))? as i8;
+                Action::CursorLeft(x, y)
+            }
+            b'r' => {
+                let x = u8::parse(bytes).map_err(|err| err.within("Action.x"))? as i8;
+                let y = u8::parse(bytes).map_err(|err| err.within("Action.y"))? as i8;
+                Action::CursorRight(x, y)
+            }
+            // `ad` alone is the D button, otherwise a direction follows
+            b'd' => match bytes.next() {
+                None => Action::D,
+                Some(next) => Action::Direction(Direction::from_bytes([next])?),
+            },
+            b'>' => Action::Left,
+            b'<' => Action::Right,
+            b'a' => Action::A,
+            b'b' => Action::B,
+            b'c' => Action::C,
+            b'!' => Action::Start,
+            b'@' => Action::Select,
+            b'q' => Action::Quit,
+            next => return Err(ParseError::Unexpected(next)),
+        };
+
+        Ok(action)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Resize(pub u16, pub u16);
 
+impl<B> Parse<B> for Resize
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        match ParseError::next(bytes)? {
+            b'r' => (),
+            next => return Err(ParseError::Unexpected(next)),
+        }
+
+        let width = u16::parse(bytes).map_err(|err| err.within("Resize.width"))?;
+        let height = u16::parse(bytes).map_err(|err| err.within("Resize.height"))?;
+        Ok(Self(width, height))
+    }
+}
+
 impl std::fmt::Display for Resize {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         struct Wrap(u16);
 
         impl std::fmt::Display for Wrap {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                print_byte((self.0 >> 8 & 0xFF) as u8, f)?;
-                print_byte((self.0 & 0xFF) as u8, f)
+                hex::write_u16(self.0, f)
             }
         }
 
@@ -104,10 +162,94 @@ impl std::fmt::Display for Resize {
     }
 }
 
+pub trait Input {
+    fn action(&mut self, action: Action);
+
+    fn resize(&mut self, resize: Resize);
+}
+
+pub fn parse_event<B, I>(bytes: &mut B, inp: &mut I) -> Result<bool, ParseError>
+where
+    B: Iterator<Item = u8>,
+    I: Input,
+{
+    // The longest event is `r` followed by two `u16`
+    const MAX_LEN: usize = 9;
+
+    // Events are read up to the new line first,
+    // since `ad` can be followed by either a direction or the new line
+    let mut line = [0; MAX_LEN];
+    let mut len = 0;
+    loop {
+        match bytes.next() {
+            None if len == 0 => return Ok(false),
+            None => return Err(ParseError::NotNewLine),
+            Some(b'\n') if len == 0 => return Err(ParseError::Unexpected(b'\n')),
+            Some(b'\n') => break,
+            Some(_) if len == MAX_LEN => return Err(ParseError::NotNewLine),
+            Some(next) => {
+                line[len] = next;
+                len += 1;
+            }
+        }
+    }
+
+    let mut line = line[..len].iter().copied();
+    match line.clone().next() {
+        Some(b'a') => {
+            let action = Action::parse(&mut line)?;
+            inp.action(action)
+        }
+        Some(b'r') => {
+            let resize = Resize::parse(&mut line)?;
+            inp.resize(resize)
+        }
+        Some(next) => return Err(ParseError::Unexpected(next)),
+        None => unreachable!(),
+    }
+
+    match line.next() {
+        None => Ok(true),
+        Some(_) => Err(ParseError::NotNewLine),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const ACTIONS: [Action; 13] = [
+        Action::Direction(Direction::Left),
+        Action::Direction(Direction::Right),
+        Action::Direction(Direction::Up),
+        Action::Direction(Direction::Down),
+        Action::Left,
+        Action::Right,
+        Action::A,
+        Action::B,
+        Action::C,
+        Action::D,
+        Action::Start,
+        Action::Select,
+        Action::Quit,
+    ];
+
+    #[derive(Default)]
+    struct Events {
+        actions: Vec<Action>,
+        resizes: Vec<Resize>,
+    }
+
+    impl Input for Events {
+        fn action(&mut self, action: Action) {
+            self.actions.push(action)
+        }
+
+        fn resize(&mut self, resize: Resize) {
+            self.resizes.push(resize)
+        }
+    }
+
     #[test]
     fn action() {
         let actual = Action::CursorLeft(0x12u8 as i8, 0xAFu8 as i8).to_string();
@@ -129,4 +271,70 @@ mod tests {
         let expected = "r1234abef";
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn parse_action() {
+        for action in ACTIONS {
+            let actual = Action::from_bytes(action.to_string().into_bytes());
+            assert_eq!(actual, Ok(action));
+        }
+
+        for x in i8::MIN..=i8::MAX {
+            for y in i8::MIN..=i8::MAX {
+                for action in [Action::CursorLeft(x, y), Action::CursorRight(x, y)] {
+                    let actual = Action::from_bytes(action.to_string().into_bytes());
+                    assert_eq!(actual, Ok(action));
+                }
+            }
+        }
+
+        let actual = Action::from_bytes(*b"adx");
+        let expected = Err(ParseError::Unexpected(b'x'));
+        assert_eq!(actual, expected);
+
+        let actual = Action::from_bytes(*b"al12");
+        let expected = Err(ParseError::End.within("Action.y"));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_resize() {
+        for width in (0..=u16::MAX).step_by(251).chain([u16::MAX]) {
+            for height in (0..=u16::MAX).step_by(241).chain([u16::MAX]) {
+                let resize = Resize(width, height);
+                let actual = Resize::from_bytes(resize.to_string().into_bytes());
+                assert_eq!(actual, Ok(resize));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_events() {
+        let resize = Resize(0x1234, 0xABEF);
+        let mut stream = String::new();
+        for action in ACTIONS {
+            stream += &format!("{}\n", action);
+        }
+        stream += &format!("{}\n", resize);
+
+        let mut events = Events::default();
+        let mut bytes = stream.bytes();
+        while parse_event(&mut bytes, &mut events).unwrap() {}
+
+        assert_eq!(events.actions, ACTIONS);
+        assert_eq!(events.resizes, [resize]);
+
+        let mut events = Events::default();
+        let actual = parse_event(&mut b"aqq\n".iter().copied(), &mut events);
+        let expected = Err(ParseError::NotNewLine);
+        assert_eq!(actual, expected);
+
+        let actual = parse_event(&mut b"x\n".iter().copied(), &mut events);
+        let expected = Err(ParseError::Unexpected(b'x'));
+        assert_eq!(actual, expected);
+
+        let actual = parse_event(&mut b"aq".iter().copied(), &mut events);
+        let expected = Err(ParseError::NotNewLine);
+        assert_eq!(actual, expected);
+    }
 }
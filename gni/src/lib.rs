@@ -1,18 +1,36 @@
+pub mod binary;
+mod blend;
 mod color;
+mod depth;
 mod hex;
 mod image;
 pub mod input;
+mod limits;
+pub mod lint;
+mod matrix;
+mod mesh;
 mod nibble;
 pub mod output;
 mod parse;
+mod patch;
 mod point;
+pub mod raster;
+mod sampling;
+pub mod stats;
 mod triangle;
 
 pub use crate::{
+    blend::Blend,
     color::Col,
+    depth::Depth,
     image::Img,
+    limits::{Limits, Usage},
+    matrix::Mat,
+    mesh::{Elems, Primitive, Verts},
     nibble::Nib,
     parse::{Parse, ParseError},
+    patch::Patch,
     point::Pnt,
+    sampling::Sampling,
     triangle::Tri,
 };
@@ -0,0 +1,144 @@
+/// Number of images held by an output and their memory in bytes, as counted by [`Img::memory`],
+/// and memory of vertex buffers, as counted by [`Verts::memory`].
+///
+/// [`Img::memory`]: crate::Img::memory
+/// [`Verts::memory`]: crate::Verts::memory
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Usage {
+    pub images: usize,
+    pub memory: usize,
+    pub vertices: usize,
+}
+
+impl Usage {
+    /// Returns the usage after the image of the memory is added in place of the replaced one.
+    pub fn add(self, memory: usize, replaced: Option<usize>) -> Self {
+        match replaced {
+            Some(old) => Self {
+                memory: self.memory - old + memory,
+                ..self
+            },
+            None => Self {
+                images: self.images + 1,
+                memory: self.memory + memory,
+                ..self
+            },
+        }
+    }
+
+    /// Returns the usage after the image of the memory is removed.
+    pub fn remove(self, memory: usize) -> Self {
+        Self {
+            images: self.images - 1,
+            memory: self.memory - memory,
+            ..self
+        }
+    }
+
+    /// Returns the usage after the vertex buffer of the memory is added in place of the replaced one.
+    pub fn add_vertices(self, memory: usize, replaced: Option<usize>) -> Self {
+        Self {
+            vertices: self.vertices - replaced.unwrap_or(0) + memory,
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for Usage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} images of {} bytes, {} bytes of vertices",
+            self.images, self.memory, self.vertices,
+        )
+    }
+}
+
+/// Limits of images and vertex buffers held by an output, so a client can't exhaust the memory.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Limits {
+    pub images: usize,
+    pub memory: usize,
+    /// Memory of vertex buffers in bytes.
+    pub vertices: usize,
+}
+
+impl Limits {
+    pub fn allows(self, usage: Usage) -> bool {
+        usage.images <= self.images
+            && usage.memory <= self.memory
+            && usage.vertices <= self.vertices
+    }
+}
+
+/// Allows every image index, 64 MiB of images and 16 MiB of vertex buffers.
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            images: 255,
+            memory: 64 << 20,
+            vertices: 16 << 20,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage() {
+        let usage = Usage::default()
+            .add(10, None)
+            .add(20, None)
+            .add(5, Some(10));
+        let expected = Usage {
+            images: 2,
+            memory: 25,
+            vertices: 0,
+        };
+        assert_eq!(usage, expected);
+
+        let actual = usage.remove(20);
+        let expected = Usage {
+            images: 1,
+            memory: 5,
+            vertices: 0,
+        };
+        assert_eq!(actual, expected);
+
+        let actual = usage.add_vertices(10, None).add_vertices(4, Some(10));
+        let expected = Usage {
+            vertices: 4,
+            ..usage
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn allows() {
+        let limits = Limits {
+            images: 2,
+            memory: 100,
+            vertices: 10,
+        };
+
+        assert!(limits.allows(Usage {
+            images: 2,
+            memory: 100,
+            vertices: 10,
+        }));
+        assert!(!limits.allows(Usage {
+            images: 3,
+            ..Usage::default()
+        }));
+        assert!(!limits.allows(Usage {
+            memory: 101,
+            ..Usage::default()
+        }));
+        assert!(!limits.allows(Usage {
+            vertices: 11,
+            ..Usage::default()
+        }));
+    }
+}
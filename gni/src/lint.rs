@@ -0,0 +1,426 @@
+use crate::{output::Output, Blend, Col, Depth, Elems, Img, Mat, Nib, Patch, Tri, Verts};
+use std::collections::HashMap;
+
+/// A mistake found by [`Lint`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Issue {
+    /// The image set for drawing was never loaded, or was removed.
+    UnknownImage(u8),
+    /// A triangle is drawn before the frame is cleared.
+    NotCleared,
+    /// The palette entry is used but was never set, so it is black.
+    UnsetColor(Nib),
+    /// All points of the triangle are on a line.
+    Degenerate,
+    /// The triangle has texture coordinates, but no image is set, so they're outside of any.
+    NoImage,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Issue::UnknownImage(idx) => write!(f, "image {:02x} isn't loaded", idx),
+            Issue::NotCleared => write!(f, "triangle drawn before the frame is cleared"),
+            Issue::UnsetColor(idx) => write!(f, "palette entry {} is used but never set", idx),
+            Issue::Degenerate => write!(f, "triangle has zero area"),
+            Issue::NoImage => write!(f, "triangle has texture coordinates but no image is set"),
+        }
+    }
+}
+
+/// An issue with the number of the command it was found at.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// Number of the command, starting from 1. Commands of the text protocol are lines,
+    /// so it's also the line number if failed commands are [skipped](Lint::skip).
+    pub command: usize,
+    pub issue: Issue,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "command {}: {}", self.command, self.issue)
+    }
+}
+
+/// Checks commands for mistakes the parser doesn't catch, passing them to the output.
+///
+/// An unset palette entry and a missing clear are reported once, the latter once per frame.
+/// Palette entries are used by vertices, clears and texels of the set image within the bounds
+/// of the triangle texture coordinates, as sampled by the nearest sampling.
+/// Texture coordinates are fractions of the image size, so they are outside of it only
+/// if there is no image.
+pub struct Lint<O> {
+    out: O,
+    command: usize,
+    warnings: Vec<Warning>,
+    images: HashMap<u8, Img>,
+    /// The set image, 0 if it isn't loaded.
+    active: u8,
+    vertices: HashMap<u8, Verts>,
+    colors: [bool; 16],
+    reported: [bool; 16],
+    cleared: bool,
+}
+
+impl<O> Lint<O> {
+    pub fn new(out: O) -> Self {
+        Self {
+            out,
+            command: 0,
+            warnings: Vec::new(),
+            images: HashMap::default(),
+            active: 0,
+            vertices: HashMap::default(),
+            colors: [false; 16],
+            reported: [false; 16],
+            cleared: false,
+        }
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Returns the warnings found since the last call.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn into_inner(self) -> O {
+        self.out
+    }
+
+    /// Counts a command that failed to decode, so numbers of later commands match the input.
+    pub fn skip(&mut self) {
+        self.command += 1;
+    }
+
+    fn warn(&mut self, issue: Issue) {
+        self.warnings.push(Warning {
+            command: self.command,
+            issue,
+        });
+    }
+
+    fn use_color(&mut self, idx: Nib) {
+        let i = idx.get() as usize;
+        if !self.colors[i] && !self.reported[i] {
+            self.reported[i] = true;
+            self.warn(Issue::UnsetColor(idx));
+        }
+    }
+
+    fn check_triangle(&mut self, Tri(points): Tri) {
+        if !self.cleared {
+            self.cleared = true;
+            self.warn(Issue::NotCleared);
+        }
+
+        for p in points {
+            self.use_color(p.col);
+        }
+
+        if points.iter().any(|p| p.tex != [0, 0]) && self.active == 0 {
+            self.warn(Issue::NoImage);
+        }
+
+        self.check_texels(points.map(|p| p.tex));
+
+        let [a, b, c] = points.map(|p| p.pos.map(|c| c as i64));
+        let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let cross = [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ];
+        if cross == [0; 3] {
+            self.warn(Issue::Degenerate);
+        }
+    }
+
+    /// Checks palette entries of texels within the bounds of the texture coordinates.
+    fn check_texels(&mut self, tex: [[u8; 2]; 3]) {
+        let img = match self.images.get(&self.active) {
+            Some(img) if img.palette().is_none() => img,
+            _ => return,
+        };
+
+        // Empty images have no texels to check
+        let (w, h) = img.size();
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let checked = self.colors.iter().zip(&self.reported);
+        if checked.clone().all(|(&set, &reported)| set || reported) {
+            return;
+        }
+
+        // Texels sampled at the coordinate, shifted by half of its step as by the renderers
+        let texel = |t: u8, len: u16| (2 * t as usize + 1) * len as usize / 512;
+        let [xs, ys] = [0, 1].map(|i| {
+            let [a, b, c] = tex.map(|t| texel(t[i], if i == 0 { w } else { h }));
+            a.min(b).min(c)..=a.max(b).max(c)
+        });
+
+        let mut used = [false; 16];
+        for y in ys {
+            let row = &img.data()[y * w as usize..][..w as usize];
+            for &idx in &row[xs.clone()] {
+                used[idx as usize] = true;
+            }
+        }
+
+        if img.sampling().transparent {
+            used[0] = false;
+        }
+
+        for (i, &used) in used.iter().enumerate() {
+            if used {
+                self.use_color(Nib::new(i as u8).unwrap());
+            }
+        }
+    }
+}
+
+impl<O> Output for Lint<O>
+where
+    O: Output,
+{
+    fn palette(&mut self, idx: Nib, col: Col) {
+        self.command += 1;
+        self.colors[idx.get() as usize] = true;
+        self.out.palette(idx, col)
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.command += 1;
+        self.out.alpha(idx, alpha)
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.command += 1;
+        self.out.blend(blend)
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        self.command += 1;
+        self.cleared = true;
+        self.use_color(idx);
+        self.out.clear(idx)
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        self.command += 1;
+        self.check_triangle(tri);
+        self.out.draw_triangle(tri)
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        self.command += 1;
+        self.vertices.insert(idx, verts.clone());
+        self.out.vertices(idx, verts)
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        self.command += 1;
+        if let Some(verts) = self.vertices.get(&idx) {
+            let tris: Vec<_> = elems.triangles(verts.points()).collect();
+            for tri in tris {
+                self.check_triangle(tri);
+            }
+        }
+
+        self.out.draw_elements(idx, elems)
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        self.command += 1;
+        self.images.insert(idx, img.clone());
+        self.out.image(idx, img)
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        self.command += 1;
+        if let Some(img) = self.images.get_mut(&idx) {
+            img.update(&patch);
+        }
+
+        self.out.update_image(idx, patch)
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.command += 1;
+        self.images.remove(&idx);
+        if self.active == idx {
+            self.active = 0;
+        }
+
+        self.out.remove_image(idx)
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        self.command += 1;
+        self.active = 0;
+        if self.images.contains_key(&idx) {
+            self.active = idx;
+        } else if idx != 0 {
+            self.warn(Issue::UnknownImage(idx));
+        }
+
+        self.out.set_image(idx)
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.command += 1;
+        self.out.depth(depth)
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.command += 1;
+        self.out.matrix(mat)
+    }
+
+    fn finish(&mut self) {
+        self.command += 1;
+        self.cleared = false;
+        self.out.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{output::Command, raster::Raster, Pnt, Primitive, Sampling};
+
+    fn point(x: i16, y: i16, col: u8) -> Pnt {
+        Pnt {
+            pos: [x, y, 0],
+            tex: [0, 0],
+            col: Nib::new(col).unwrap(),
+        }
+    }
+
+    fn warnings(commands: Vec<Command>) -> Vec<Warning> {
+        let mut lint = Lint::new(Raster::new(4, 4));
+        for command in commands {
+            lint.command(command);
+        }
+
+        lint.take_warnings()
+    }
+
+    #[test]
+    fn clean() {
+        let nib = Nib::new(1).unwrap();
+        let img = Img::new([nib], (1, 1)).unwrap();
+        let actual = warnings(vec![
+            Command::Palette(nib, Col::new(1, 2, 3)),
+            Command::Clear(nib),
+            Command::Image(1, img),
+            Command::SetImage(1),
+            Command::Triangle(Tri([point(0, 0, 1), point(1, 0, 1), point(0, 1, 1)])),
+            Command::SetImage(0),
+            Command::Finish,
+        ]);
+        assert_eq!(actual, []);
+    }
+
+    #[test]
+    fn issues() {
+        let nib = Nib::new(1).unwrap();
+        let tri = Tri([point(0, 0, 1), point(1, 0, 1), point(0, 1, 2)]);
+        let line = Tri([point(0, 0, 1), point(1, 1, 1), point(2, 2, 1)]);
+        let actual = warnings(vec![
+            Command::Palette(nib, Col::new(1, 2, 3)),
+            Command::Triangle(tri),
+            Command::Clear(nib),
+            Command::Triangle(tri),
+            Command::Triangle(line),
+            Command::SetImage(5),
+            Command::Finish,
+            Command::Vertices(0, Verts::new(line.0).unwrap()),
+            Command::Elements(0, Elems::new(Primitive::List, [0, 1, 2]).unwrap()),
+        ]);
+        let warning = |command, issue| Warning { command, issue };
+        let expected = [
+            warning(2, Issue::NotCleared),
+            warning(2, Issue::UnsetColor(Nib::new(2).unwrap())),
+            warning(5, Issue::Degenerate),
+            warning(6, Issue::UnknownImage(5)),
+            warning(9, Issue::NotCleared),
+            warning(9, Issue::Degenerate),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn textures() {
+        let nib = |n| Nib::new(n).unwrap();
+        let textured = |u: [u8; 3]| {
+            let mut tri = Tri([point(0, 0, 1), point(1, 0, 1), point(0, 1, 1)]);
+            for (p, u) in tri.0.iter_mut().zip(u) {
+                p.tex = [u, 0];
+            }
+
+            Command::Triangle(tri)
+        };
+        let img = Img::new([0, 1, 2, 3].map(nib), (4, 1)).unwrap();
+        let transparent = Sampling {
+            transparent: true,
+            ..Sampling::default()
+        };
+        let actual = warnings(vec![
+            Command::Palette(nib(1), Col::new(1, 2, 3)),
+            Command::Clear(nib(1)),
+            textured([0, 0, 0x10]),
+            Command::Image(1, img.with_sampling(transparent)),
+            Command::SetImage(1),
+            // Texels 0 and 1, the former is transparent
+            textured([0x00, 0x7F, 0x10]),
+            // Texels 1 to 3
+            textured([0x40, 0xFF, 0x80]),
+            Command::RemoveImage(1),
+            textured([0x40, 0xFF, 0x80]),
+        ]);
+        let warning = |command, issue| Warning { command, issue };
+        let expected = [
+            warning(3, Issue::NoImage),
+            warning(7, Issue::UnsetColor(nib(2))),
+            warning(7, Issue::UnsetColor(nib(3))),
+            warning(9, Issue::NoImage),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn empty_textures() {
+        let nib = |n| Nib::new(n).unwrap();
+        let mut tri = Tri([point(0, 0, 1), point(1, 0, 1), point(0, 1, 1)]);
+        tri.0[1].tex = [0xFF, 0xFF];
+        let actual = warnings(vec![
+            Command::Palette(nib(1), Col::new(1, 2, 3)),
+            Command::Clear(nib(1)),
+            Command::Image(1, Img::new([], (0, 0)).unwrap()),
+            Command::Image(2, Img::new([], (0, 10)).unwrap()),
+            Command::SetImage(1),
+            Command::Triangle(tri),
+            Command::SetImage(2),
+            Command::Triangle(tri),
+        ]);
+        assert_eq!(actual, []);
+    }
+
+    #[test]
+    fn display() {
+        let actual = Warning {
+            command: 3,
+            issue: Issue::UnknownImage(0xAB),
+        }
+        .to_string();
+        let expected = "command 3: image ab isn't loaded";
+        assert_eq!(actual, expected);
+    }
+}
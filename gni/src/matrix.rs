@@ -0,0 +1,94 @@
+use crate::{hex, Parse, ParseError};
+
+/// Transformation matrix of the 16.16 fixed point numbers, row by row.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Mat(pub [[i32; 4]; 4]);
+
+impl Mat {
+    pub const ONE: i32 = 1 << 16;
+
+    pub fn identity() -> Self {
+        let mut mat = [[0; 4]; 4];
+        for (i, row) in mat.iter_mut().enumerate() {
+            row[i] = Self::ONE;
+        }
+
+        Self(mat)
+    }
+
+    pub fn from_f32(mat: [[f32; 4]; 4]) -> Self {
+        Self(mat.map(|row| row.map(|v| (v * Self::ONE as f32).round() as i32)))
+    }
+
+    pub fn to_f32(self) -> [[f32; 4]; 4] {
+        self.0.map(|row| row.map(|v| v as f32 / Self::ONE as f32))
+    }
+}
+
+impl Default for Mat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl<B> Parse<B> for Mat
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let mut mat = [[0; 4]; 4];
+        for v in mat.iter_mut().flatten() {
+            *v = u32::parse(bytes).map_err(|err| err.within("Mat"))? as i32;
+        }
+
+        Ok(Self(mat))
+    }
+}
+
+impl std::fmt::Display for Mat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for v in self.0.iter().flatten() {
+            hex::write_u32(*v as u32, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let actual = Mat::from_bytes(
+            *b"00010000000000000000000000000000\
+            0000000000008000000000000000000f\
+            00000000000000000001000000000000\
+            ffff0000000000000000000000010000",
+        );
+
+        let expected = Ok(Mat([
+            [Mat::ONE, 0, 0, 0],
+            [0, Mat::ONE / 2, 0, 0xF],
+            [0, 0, Mat::ONE, 0],
+            [-Mat::ONE, 0, 0, Mat::ONE],
+        ]));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        let mat = Mat::from_f32([
+            [1., 0., 0., 0.5],
+            [0., -2., 0., 0.],
+            [0., 0., 0.25, 0.],
+            [0., 0., -1., 0.],
+        ]);
+
+        let actual = Mat::from_bytes(mat.to_string().into_bytes());
+        assert_eq!(actual, Ok(mat));
+        assert_eq!(mat.to_f32()[1][1], -2.);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{hex, Parse, ParseError, Pnt, Tri};
+
+/// Vertex buffer, points referenced by indices of [`Elems`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Verts(Box<[Pnt]>);
+
+impl Verts {
+    /// Returns `None` if there are more than `u16::MAX` points, which the commands can't encode.
+    pub fn new<P>(points: P) -> Option<Self>
+    where
+        P: Into<Box<[Pnt]>>,
+    {
+        let points = points.into();
+        if points.len() > u16::MAX as usize {
+            return None;
+        }
+
+        Some(Self(points))
+    }
+
+    pub fn points(&self) -> &[Pnt] {
+        &self.0
+    }
+
+    /// Memory of the points in bytes.
+    pub fn memory(&self) -> usize {
+        std::mem::size_of_val(&*self.0)
+    }
+}
+
+impl<B> Parse<B> for Verts
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let len = u16::parse(bytes).map_err(|err| err.within("Verts.len"))?;
+        let mut points = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            points.push(Pnt::parse(bytes)?);
+        }
+
+        Ok(Self(points.into()))
+    }
+}
+
+/// Displays the number of points and the points.
+impl std::fmt::Display for Verts {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u16(self.0.len() as u16, f)?;
+        for p in self.0.iter() {
+            write!(f, "{}", p)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How indices of [`Elems`] form triangles.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Primitive {
+    /// Every three indices are a triangle.
+    #[default]
+    List,
+    /// Every index forms a triangle with the two previous ones.
+    /// The winding of every second triangle is flipped, so all of them face the same side.
+    Strip,
+}
+
+impl Primitive {
+    const MODES: [Self; 2] = [Self::List, Self::Strip];
+
+    pub fn new(mode: u8) -> Option<Self> {
+        Self::MODES.get(mode as usize).copied()
+    }
+
+    pub fn get(self) -> u8 {
+        self as u8
+    }
+}
+
+impl<B> Parse<B> for Primitive
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let byte = ParseError::next(bytes)?;
+        let mode = hex::read_u4(byte)?;
+        Self::new(mode).ok_or(ParseError::Unexpected(byte))
+    }
+}
+
+impl std::fmt::Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u4(self.get(), f)
+    }
+}
+
+/// Indexed triangles drawn from a vertex buffer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Elems {
+    primitive: Primitive,
+    indices: Box<[u16]>,
+}
+
+impl Elems {
+    /// Returns `None` if there are more than `u16::MAX` indices, which the commands can't encode.
+    pub fn new<I>(primitive: Primitive, indices: I) -> Option<Self>
+    where
+        I: Into<Box<[u16]>>,
+    {
+        let indices = indices.into();
+        if indices.len() > u16::MAX as usize {
+            return None;
+        }
+
+        Some(Self { primitive, indices })
+    }
+
+    pub fn primitive(&self) -> Primitive {
+        self.primitive
+    }
+
+    /// Indices of points in the vertex buffer.
+    pub fn indices(&self) -> &[u16] {
+        &self.indices
+    }
+
+    /// Returns triangles of the points. Triangles with indices out of the points are skipped.
+    pub fn triangles<'a>(&'a self, points: &'a [Pnt]) -> impl Iterator<Item = Tri> + 'a {
+        let indices: Box<dyn Iterator<Item = [u16; 3]>> = match self.primitive {
+            Primitive::List => Box::new(self.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])),
+            Primitive::Strip => Box::new(self.indices.windows(3).enumerate().map(|(i, t)| {
+                if i % 2 == 0 {
+                    [t[0], t[1], t[2]]
+                } else {
+                    [t[1], t[0], t[2]]
+                }
+            })),
+        };
+
+        indices.filter_map(move |[a, b, c]| {
+            let point = |i: u16| points.get(i as usize).copied();
+            Some(Tri([point(a)?, point(b)?, point(c)?]))
+        })
+    }
+}
+
+impl<B> Parse<B> for Elems
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let primitive = Primitive::parse(bytes).map_err(|err| err.within("Elems.primitive"))?;
+        let len = u16::parse(bytes).map_err(|err| err.within("Elems.len"))?;
+        let mut indices = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let idx = u16::parse(bytes).map_err(|err| err.within("Elems.indices"))?;
+            indices.push(idx);
+        }
+
+        Ok(Self {
+            primitive,
+            indices: indices.into(),
+        })
+    }
+}
+
+impl std::fmt::Display for Elems {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.primitive)?;
+        hex::write_u16(self.indices.len() as u16, f)?;
+        for &idx in self.indices.iter() {
+            hex::write_u16(idx, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Nib;
+
+    fn point(x: i16) -> Pnt {
+        Pnt {
+            pos: [x, 0, 0],
+            tex: [0, 0],
+            col: Nib::new(1).unwrap(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let actual = Verts::from_bytes(*b"00010001000200030405f");
+        let expected = Ok(Verts::new([Pnt {
+            pos: [1, 2, 3],
+            tex: [4, 5],
+            col: Nib::new(0xF).unwrap(),
+        }])
+        .unwrap());
+        assert_eq!(actual, expected);
+
+        let actual = Elems::from_bytes(*b"10003000100020003");
+        let expected = Ok(Elems::new(Primitive::Strip, [1, 2, 3]).unwrap());
+        assert_eq!(actual, expected);
+
+        let actual = Elems::from_bytes(*b"2");
+        let expected = Err(ParseError::Unexpected(b'2').within("Elems.primitive"));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        let actual = Verts::new([point(-1)]).unwrap().to_string();
+        let expected = "0001ffff0000000000001";
+        assert_eq!(actual, expected);
+
+        let actual = Elems::new(Primitive::List, [0, 0xABCD])
+            .unwrap()
+            .to_string();
+        let expected = "000020000abcd";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn triangles() {
+        let points: Vec<_> = (0..4).map(point).collect();
+        let tri = |a, b, c| Tri([point(a), point(b), point(c)]);
+
+        let elems = Elems::new(Primitive::List, [0, 1, 2, 3, 2, 1, 0, 9, 1, 3]).unwrap();
+        let actual: Vec<_> = elems.triangles(&points).collect();
+        let expected = [tri(0, 1, 2), tri(3, 2, 1)];
+        assert_eq!(actual, expected);
+
+        let elems = Elems::new(Primitive::Strip, [0, 1, 2, 3]).unwrap();
+        let actual: Vec<_> = elems.triangles(&points).collect();
+        let expected = [tri(0, 1, 2), tri(2, 1, 3)];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn bounds() {
+        assert!(Verts::new(vec![point(0); u16::MAX as usize]).is_some());
+        assert!(Verts::new(vec![point(0); u16::MAX as usize + 1]).is_none());
+        assert!(Elems::new(Primitive::List, vec![0; u16::MAX as usize + 1]).is_none());
+    }
+}
@@ -27,6 +27,12 @@ where
     }
 }
 
+impl std::fmt::Display for Nib {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u4(self.0, f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +47,11 @@ mod tests {
         let expected = Err(ParseError::Byte(b'q'));
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn display() {
+        let actual = Nib::new(0x0A).unwrap().to_string();
+        let expected = "a";
+        assert_eq!(actual, expected);
+    }
 }
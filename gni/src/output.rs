@@ -1,68 +1,1043 @@
-use crate::{Col, Img, Nib, Parse, ParseError, Tri};
+use crate::{
+    hex, Blend, Col, Depth, Elems, Img, Limits, Mat, Nib, Parse, ParseError, Patch, Sampling, Tri,
+    Verts,
+};
 
+/// Receiver of output commands.
+///
+/// Every command method is required, so an implementation doesn't silently ignore commands
+/// added to the protocol. Adapters forward each of them explicitly.
 pub trait Output {
     fn palette(&mut self, idx: Nib, col: Col);
 
+    /// Sets the palette entry alpha. Fragments with the zero alpha are discarded.
+    /// Transparent texels of an image are set by [`Sampling::transparent`] instead,
+    /// so the entry can still be opaque elsewhere.
+    fn alpha(&mut self, idx: Nib, alpha: u8);
+
+    fn blend(&mut self, blend: Blend);
+
     fn clear(&mut self, idx: Nib);
 
     fn draw_triangle(&mut self, tri: Tri);
 
+    /// Adds or replaces the vertex buffer.
+    fn vertices(&mut self, idx: u8, verts: Verts);
+
+    /// Draws triangles of the vertex buffer, see [`Elems::triangles`].
+    /// Nothing is drawn if there is no such buffer.
+    fn draw_elements(&mut self, idx: u8, elems: Elems);
+
+    /// Adds or replaces the image. Texels are sampled as set by [`Img::sampling`].
     fn image(&mut self, idx: u8, img: Img);
 
+    /// Updates a region of the image. A patch that doesn't [fit](Patch::fits) the image is ignored.
+    fn update_image(&mut self, idx: u8, patch: Patch);
+
+    /// Frees the image. Removing the set image switches to drawing without a texture.
+    fn remove_image(&mut self, idx: u8);
+
     fn set_image(&mut self, idx: u8);
 
+    fn depth(&mut self, depth: Depth);
+
+    fn matrix(&mut self, mat: Mat);
+
     fn finish(&mut self);
+
+    fn command(&mut self, command: Command) {
+        match command {
+            Command::Palette(idx, col) => self.palette(idx, col),
+            Command::Alpha(idx, alpha) => self.alpha(idx, alpha),
+            Command::Blend(blend) => self.blend(blend),
+            Command::Clear(idx) => self.clear(idx),
+            Command::Triangle(tri) => self.draw_triangle(tri),
+            Command::Vertices(idx, verts) => self.vertices(idx, verts),
+            Command::Elements(idx, elems) => self.draw_elements(idx, elems),
+            Command::Image(idx, img) => self.image(idx, img),
+            Command::UpdateImage(idx, patch) => self.update_image(idx, patch),
+            Command::RemoveImage(idx) => self.remove_image(idx),
+            Command::SetImage(idx) => self.set_image(idx),
+            Command::Depth(depth) => self.depth(depth),
+            Command::Matrix(mat) => self.matrix(mat),
+            Command::Finish => self.finish(),
+        }
+    }
 }
 
-pub fn parse_command<B, O>(bytes: &mut B, out: &mut O) -> Result<bool, ParseError>
+/// Discards all commands, for adapters used only for their checks, such as [`Lint`].
+///
+/// [`Lint`]: crate::lint::Lint
+impl Output for () {
+    fn palette(&mut self, _: Nib, _: Col) {}
+
+    fn alpha(&mut self, _: Nib, _: u8) {}
+
+    fn blend(&mut self, _: Blend) {}
+
+    fn clear(&mut self, _: Nib) {}
+
+    fn draw_triangle(&mut self, _: Tri) {}
+
+    fn vertices(&mut self, _: u8, _: Verts) {}
+
+    fn draw_elements(&mut self, _: u8, _: Elems) {}
+
+    fn image(&mut self, _: u8, _: Img) {}
+
+    fn update_image(&mut self, _: u8, _: Patch) {}
+
+    fn remove_image(&mut self, _: u8) {}
+
+    fn set_image(&mut self, _: u8) {}
+
+    fn depth(&mut self, _: Depth) {}
+
+    fn matrix(&mut self, _: Mat) {}
+
+    fn finish(&mut self) {}
+}
+
+impl<T> Output for &mut T
+where
+    T: Output + ?Sized,
+{
+    fn palette(&mut self, idx: Nib, col: Col) {
+        (**self).palette(idx, col)
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        (**self).alpha(idx, alpha)
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        (**self).blend(blend)
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        (**self).clear(idx)
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        (**self).draw_triangle(tri)
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        (**self).vertices(idx, verts)
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        (**self).draw_elements(idx, elems)
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        (**self).image(idx, img)
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        (**self).update_image(idx, patch)
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        (**self).remove_image(idx)
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        (**self).set_image(idx)
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        (**self).depth(depth)
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        (**self).matrix(mat)
+    }
+
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+
+    fn command(&mut self, command: Command) {
+        (**self).command(command)
+    }
+}
+
+impl<T> Output for Box<T>
+where
+    T: Output + ?Sized,
+{
+    fn palette(&mut self, idx: Nib, col: Col) {
+        (**self).palette(idx, col)
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        (**self).alpha(idx, alpha)
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        (**self).blend(blend)
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        (**self).clear(idx)
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        (**self).draw_triangle(tri)
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        (**self).vertices(idx, verts)
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        (**self).draw_elements(idx, elems)
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        (**self).image(idx, img)
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        (**self).update_image(idx, patch)
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        (**self).remove_image(idx)
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        (**self).set_image(idx)
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        (**self).depth(depth)
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        (**self).matrix(mat)
+    }
+
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+
+    fn command(&mut self, command: Command) {
+        (**self).command(command)
+    }
+}
+
+/// Passes every command to both outputs, e.g. to render and record a stream in a single pass.
+pub struct Tee<A, B>(pub A, pub B);
+
+impl<A, B> Output for Tee<A, B>
+where
+    A: Output,
+    B: Output,
+{
+    fn palette(&mut self, idx: Nib, col: Col) {
+        self.command(Command::Palette(idx, col))
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.command(Command::Alpha(idx, alpha))
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.command(Command::Blend(blend))
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        self.command(Command::Clear(idx))
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        self.command(Command::Triangle(tri))
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        self.command(Command::Vertices(idx, verts))
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        self.command(Command::Elements(idx, elems))
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        self.command(Command::Image(idx, img))
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        self.command(Command::UpdateImage(idx, patch))
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.command(Command::RemoveImage(idx))
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        self.command(Command::SetImage(idx))
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.command(Command::Depth(depth))
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.command(Command::Matrix(mat))
+    }
+
+    fn finish(&mut self) {
+        self.command(Command::Finish)
+    }
+
+    fn command(&mut self, command: Command) {
+        self.0.command(command.clone());
+        self.1.command(command)
+    }
+}
+
+/// Passes every command to all outputs in order.
+impl<'a> Output for Vec<Box<dyn Output + 'a>> {
+    fn palette(&mut self, idx: Nib, col: Col) {
+        self.command(Command::Palette(idx, col))
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.command(Command::Alpha(idx, alpha))
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.command(Command::Blend(blend))
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        self.command(Command::Clear(idx))
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        self.command(Command::Triangle(tri))
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        self.command(Command::Vertices(idx, verts))
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        self.command(Command::Elements(idx, elems))
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        self.command(Command::Image(idx, img))
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        self.command(Command::UpdateImage(idx, patch))
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.command(Command::RemoveImage(idx))
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        self.command(Command::SetImage(idx))
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.command(Command::Depth(depth))
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.command(Command::Matrix(mat))
+    }
+
+    fn finish(&mut self) {
+        self.command(Command::Finish)
+    }
+
+    fn command(&mut self, command: Command) {
+        if let Some((last, rest)) = self.split_last_mut() {
+            for out in rest {
+                out.command(command.clone());
+            }
+
+            last.command(command)
+        }
+    }
+}
+
+/// A command of the output protocol.
+///
+/// Displays without the trailing new line, so a stream is written command by command with `writeln!`.
+/// An image is displayed with the extended `x` command if it doesn't fit the compact `i` one.
+/// Note that an image with the zero index is displayed as is, but is rejected by [`parse_command`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Palette(Nib, Col),
+    Alpha(Nib, u8),
+    Blend(Blend),
+    Clear(Nib),
+    Triangle(Tri),
+    Vertices(u8, Verts),
+    Elements(u8, Elems),
+    Image(u8, Img),
+    UpdateImage(u8, Patch),
+    RemoveImage(u8),
+    SetImage(u8),
+    Depth(Depth),
+    Matrix(Mat),
+    Finish,
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Command::Palette(idx, col) => write!(f, "p{}{}", idx, col),
+            Command::Alpha(idx, alpha) => {
+                write!(f, "a{}", idx)?;
+                hex::write_u8(*alpha, f)
+            }
+            Command::Blend(blend) => write!(f, "b{}", blend),
+            Command::Clear(idx) => write!(f, "c{}", idx),
+            Command::Triangle(tri) => write!(f, "t{}", tri),
+            Command::Vertices(idx, verts) => {
+                write!(f, "v")?;
+                hex::write_u8(*idx, f)?;
+                write!(f, "{}", verts)
+            }
+            Command::Elements(idx, elems) => {
+                write!(f, "e")?;
+                hex::write_u8(*idx, f)?;
+                write!(f, "{}", elems)
+            }
+            Command::Image(idx, img) => {
+                write!(f, "{}", if img.is_compact() { 'i' } else { 'x' })?;
+                hex::write_u8(*idx, f)?;
+                write!(f, "{}", img)?;
+                let sampling = img.sampling();
+                if sampling != Sampling::default() {
+                    write!(f, "{}", sampling)?;
+                }
+
+                Ok(())
+            }
+            Command::UpdateImage(idx, patch) => {
+                write!(f, "u")?;
+                hex::write_u8(*idx, f)?;
+                write!(f, "{}", patch)
+            }
+            Command::RemoveImage(idx) => {
+                write!(f, "ri")?;
+                hex::write_u8(*idx, f)
+            }
+            Command::SetImage(idx) => {
+                write!(f, "si")?;
+                hex::write_u8(*idx, f)
+            }
+            Command::Depth(depth) => write!(f, "d{}", depth),
+            Command::Matrix(mat) => write!(f, "m{}", mat),
+            Command::Finish => Ok(()),
+        }
+    }
+}
+
+/// Error with the position of the failed command.
+#[derive(Debug, Eq, PartialEq)]
+pub struct CommandError {
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Byte offset within the line, starting from 1.
+    pub col: usize,
+    pub error: ParseError,
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, col {}: {}", self.line, self.col, self.error)
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Counts lines and columns of read bytes.
+struct Reader<B> {
+    bytes: B,
+    line: usize,
+    col: usize,
+    last: Option<u8>,
+    end: bool,
+}
+
+impl<B> Iterator for Reader<B>
 where
     B: Iterator<Item = u8>,
-    O: Output,
 {
-    let next = match bytes.next() {
-        None => return Ok(false),
-        Some(next) => next,
-    };
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match self.bytes.next() {
+            None => {
+                self.end = true;
+                return None;
+            }
+            Some(next) => next,
+        };
+
+        if self.last == Some(b'\n') {
+            self.line += 1;
+            self.col = 0;
+        }
+
+        self.col += 1;
+        self.last = Some(next);
+        Some(next)
+    }
+}
+
+/// Iterator over commands decoded from bytes.
+///
+/// By default the iterator stops after the first error.
+/// In the recovery mode it skips the rest of the failed line and continues.
+pub struct Commands<B> {
+    reader: Reader<B>,
+    recover: bool,
+    failed: bool,
+    max_memory: usize,
+}
+
+impl<B> Commands<B> {
+    pub fn new(bytes: B) -> Self {
+        Self {
+            reader: Reader {
+                bytes,
+                line: 1,
+                col: 0,
+                last: None,
+                end: false,
+            },
+            recover: false,
+            failed: false,
+            max_memory: usize::MAX,
+        }
+    }
 
-    match next {
+    pub fn with_recovery(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    /// Rejects images exceeding the memory limit before their texels are read.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.max_memory = limits.memory;
+        self
+    }
+
+    pub fn into_inner(self) -> B {
+        self.reader.bytes
+    }
+}
+
+impl<B> Iterator for Commands<B>
+where
+    B: Iterator<Item = u8>,
+{
+    type Item = Result<Command, CommandError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            if !self.recover {
+                return None;
+            }
+
+            self.failed = false;
+            if self.reader.last != Some(b'\n') {
+                self.reader.find(|&b| b == b'\n')?;
+            }
+        }
+
+        let next = self.reader.next()?;
+        match read_command(next, &mut self.reader, self.max_memory) {
+            Ok(command) => Some(Ok(command)),
+            Err(error) => {
+                self.failed = true;
+                let reader = &self.reader;
+                Some(Err(CommandError {
+                    line: reader.line,
+                    col: if reader.end {
+                        reader.col + 1
+                    } else {
+                        reader.col
+                    },
+                    error,
+                }))
+            }
+        }
+    }
+}
+
+fn read_command<B>(next: u8, bytes: &mut B, max_memory: usize) -> Result<Command, ParseError>
+where
+    B: Iterator<Item = u8>,
+{
+    let command = match next {
         b'p' => {
-            let idx = Nib::parse(bytes)?;
+            let idx = Nib::parse(bytes).map_err(|err| err.within("palette.idx"))?;
             let col = Col::parse(bytes)?;
-            out.palette(idx, col)
+            Command::Palette(idx, col)
+        }
+        b'a' => {
+            let idx = Nib::parse(bytes).map_err(|err| err.within("alpha.idx"))?;
+            let alpha = u8::parse(bytes).map_err(|err| err.within("alpha.value"))?;
+            Command::Alpha(idx, alpha)
+        }
+        b'b' => {
+            let blend = Blend::parse(bytes).map_err(|err| err.within("blend.mode"))?;
+            Command::Blend(blend)
         }
         b'c' => {
-            let idx = Nib::parse(bytes)?;
-            out.clear(idx)
+            let idx = Nib::parse(bytes).map_err(|err| err.within("clear.idx"))?;
+            Command::Clear(idx)
         }
         b't' => {
             let tri = Tri::parse(bytes)?;
-            out.draw_triangle(tri)
+            Command::Triangle(tri)
+        }
+        b'v' => {
+            let idx = u8::parse(bytes).map_err(|err| err.within("vertices.idx"))?;
+            let verts = Verts::parse(bytes)?;
+            Command::Vertices(idx, verts)
+        }
+        b'e' => {
+            let idx = u8::parse(bytes).map_err(|err| err.within("elements.idx"))?;
+            let elems = Elems::parse(bytes)?;
+            Command::Elements(idx, elems)
         }
-        b'i' => {
-            let idx = u8::parse(bytes)?;
+        b'i' | b'x' => {
+            let idx = u8::parse(bytes).map_err(|err| err.within("image.idx"))?;
             if idx == 0 {
                 return Err(ParseError::ZeroIndex);
             }
 
-            let img = Img::parse(bytes)?;
-            out.image(idx, img)
+            let img = if next == b'i' {
+                Img::parse_compact(bytes, max_memory)?
+            } else {
+                Img::parse_extended(bytes, max_memory)?
+            };
+
+            // The sampling is an optional digit before the new line
+            let sampling = match bytes.next() {
+                Some(b'\n') => return Ok(Command::Image(idx, img)),
+                Some(byte) => Sampling::parse(&mut std::iter::once(byte).chain(&mut *bytes))
+                    .map_err(|err| err.within("image.sampling"))?,
+                None => return Err(ParseError::NotNewLine),
+            };
+
+            Command::Image(idx, img.with_sampling(sampling))
+        }
+        b'u' => {
+            let idx = u8::parse(bytes).map_err(|err| err.within("update_image.idx"))?;
+            let patch = Patch::parse(bytes)?;
+            Command::UpdateImage(idx, patch)
         }
+        b'r' => match ParseError::next(bytes)? {
+            b'i' => {
+                let idx = u8::parse(bytes).map_err(|err| err.within("remove_image.idx"))?;
+                Command::RemoveImage(idx)
+            }
+            next => return Err(ParseError::Unexpected(next)),
+        },
         b's' => match ParseError::next(bytes)? {
             b'i' => {
-                let idx = u8::parse(bytes)?;
-                out.set_image(idx)
+                let idx = u8::parse(bytes).map_err(|err| err.within("set_image.idx"))?;
+                Command::SetImage(idx)
             }
-            next => return Err(ParseError::Byte(next)),
+            next => return Err(ParseError::Unexpected(next)),
         },
-        b'\n' => {
-            out.finish();
-            return Ok(false);
+        b'd' => {
+            let depth = Depth::parse(bytes).map_err(|err| err.within("depth.mode"))?;
+            Command::Depth(depth)
         }
-        _ => return Err(ParseError::Byte(next)),
-    }
+        b'm' => {
+            let mat = Mat::parse(bytes)?;
+            Command::Matrix(mat)
+        }
+        b'\n' => return Ok(Command::Finish),
+        _ => return Err(ParseError::Unexpected(next)),
+    };
 
     match bytes.next() {
-        Some(b'\n') => Ok(true),
+        Some(b'\n') => Ok(command),
         _ => Err(ParseError::NotNewLine),
     }
 }
+
+/// Parses a single command and passes it to the output.
+///
+/// Returns `false` at the end of the bytes or when the frame is finished.
+pub fn parse_command<B, O>(bytes: &mut B, out: &mut O) -> Result<bool, ParseError>
+where
+    B: Iterator<Item = u8>,
+    O: Output,
+{
+    let command = match bytes.next() {
+        None => return Ok(false),
+        Some(next) => read_command(next, bytes, usize::MAX)?,
+    };
+
+    let more = command != Command::Finish;
+    out.command(command);
+    Ok(more)
+}
+
+/// Passes all commands to the output, stopping at the first error.
+pub fn execute<I, O, E>(commands: I, out: &mut O) -> Result<(), E>
+where
+    I: IntoIterator<Item = Result<Command, E>>,
+    O: Output,
+{
+    for command in commands {
+        out.command(command?);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pnt, Primitive};
+
+    #[derive(Default)]
+    struct Recorder(Vec<Command>);
+
+    impl Output for Recorder {
+        fn palette(&mut self, idx: Nib, col: Col) {
+            self.0.push(Command::Palette(idx, col))
+        }
+
+        fn alpha(&mut self, idx: Nib, alpha: u8) {
+            self.0.push(Command::Alpha(idx, alpha))
+        }
+
+        fn blend(&mut self, blend: Blend) {
+            self.0.push(Command::Blend(blend))
+        }
+
+        fn clear(&mut self, idx: Nib) {
+            self.0.push(Command::Clear(idx))
+        }
+
+        fn draw_triangle(&mut self, tri: Tri) {
+            self.0.push(Command::Triangle(tri))
+        }
+
+        fn vertices(&mut self, idx: u8, verts: Verts) {
+            self.0.push(Command::Vertices(idx, verts))
+        }
+
+        fn draw_elements(&mut self, idx: u8, elems: Elems) {
+            self.0.push(Command::Elements(idx, elems))
+        }
+
+        fn image(&mut self, idx: u8, img: Img) {
+            self.0.push(Command::Image(idx, img))
+        }
+
+        fn update_image(&mut self, idx: u8, patch: Patch) {
+            self.0.push(Command::UpdateImage(idx, patch))
+        }
+
+        fn remove_image(&mut self, idx: u8) {
+            self.0.push(Command::RemoveImage(idx))
+        }
+
+        fn set_image(&mut self, idx: u8) {
+            self.0.push(Command::SetImage(idx))
+        }
+
+        fn depth(&mut self, depth: Depth) {
+            self.0.push(Command::Depth(depth))
+        }
+
+        fn matrix(&mut self, mat: Mat) {
+            self.0.push(Command::Matrix(mat))
+        }
+
+        fn finish(&mut self) {
+            self.0.push(Command::Finish)
+        }
+    }
+
+    fn commands() -> Vec<Command> {
+        let nib = |n| Nib::new(n).unwrap();
+        let point = |x, y, col| Pnt {
+            pos: [x, y, -0x10],
+            tex: [0x00, 0xFF],
+            col: nib(col),
+        };
+
+        vec![
+            Command::Palette(nib(0x0), Col::new(0x00, 0x00, 0x00)),
+            Command::Palette(nib(0xF), Col::new(0xFF, 0x80, 0x01)),
+            Command::Alpha(nib(0x1), 0x80),
+            Command::Blend(Blend::Alpha),
+            Command::Clear(nib(0xF)),
+            Command::Image(0x01, Img::new([nib(0x1), nib(0xA)], (1, 2)).unwrap()),
+            Command::Image(0xFF, Img::new([], (0, 0)).unwrap()),
+            Command::Image(
+                0x02,
+                Img::new([nib(0x3)], (1, 1))
+                    .unwrap()
+                    .with_sampling(Sampling {
+                        linear: true,
+                        clamp: true,
+                        transparent: false,
+                    }),
+            ),
+            Command::Image(0x03, Img::extended([nib(0x4); 0x100], (0x100, 1)).unwrap()),
+            Command::Image(
+                0x04,
+                Img::indexed(
+                    [0x00, 0xFF],
+                    (1, 2),
+                    Box::new([Col::new(0x01, 0x02, 0x03); 256]),
+                )
+                .unwrap()
+                .with_sampling(Sampling {
+                    linear: false,
+                    clamp: true,
+                    transparent: false,
+                }),
+            ),
+            Command::UpdateImage(0x01, Patch::new([0x0F], (0, 1), (1, 1)).unwrap()),
+            Command::SetImage(0x01),
+            Command::Triangle(Tri([
+                point(-0x100, -0x100, 0x1),
+                point(0x100, -0x100, 0x2),
+                point(0x0, 0x7FFF, 0x3),
+            ])),
+            Command::Vertices(0x02, Verts::new([point(0x1, 0x2, 0x3)]).unwrap()),
+            Command::Elements(
+                0x02,
+                Elems::new(Primitive::Strip, [0x0000, 0xFFFF]).unwrap(),
+            ),
+            Command::SetImage(0x00),
+            Command::RemoveImage(0xFF),
+            Command::Depth(Depth::LessEqual),
+            Command::Matrix(Mat::identity()),
+            Command::Finish,
+        ]
+    }
+
+    #[test]
+    fn display() {
+        let actual: Vec<_> = commands().iter().map(Command::to_string).collect();
+        let wide = format!("x03001000001{}", "4".repeat(0x100));
+        let indexed = format!("x04100010002{}00ff2", "010203".repeat(256));
+        let expected = [
+            "p0000000",
+            "pfff8001",
+            "a180",
+            "b1",
+            "cf",
+            "i0101021a",
+            "iff0000",
+            "i02010133",
+            &wide,
+            &indexed,
+            "u0100000001000100010f",
+            "si01",
+            concat!(
+                "t",
+                "ff00ff00fff000ff1",
+                "0100ff00fff000ff2",
+                "00007ffffff000ff3",
+            ),
+            "v02000100010002fff000ff3",
+            "e02100020000ffff",
+            "si00",
+            "riff",
+            "d4",
+            concat!(
+                "m",
+                "00010000000000000000000000000000",
+                "00000000000100000000000000000000",
+                "00000000000000000001000000000000",
+                "00000000000000000000000000010000",
+            ),
+            "",
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn round_trip() {
+        let commands = commands();
+        let mut stream = String::new();
+        for command in &commands {
+            stream += &format!("{}\n", command);
+        }
+
+        let mut recorder = Recorder::default();
+        let mut bytes = stream.bytes();
+        while bytes.len() > 0 {
+            parse_command(&mut bytes, &mut recorder).unwrap();
+        }
+
+        assert_eq!(recorder.0, commands);
+    }
+
+    #[test]
+    fn decode() {
+        let commands = commands();
+        let mut stream = String::new();
+        for command in &commands {
+            stream += &format!("{}\n", command);
+        }
+
+        let actual: Result<Vec<_>, _> = Commands::new(stream.bytes()).collect();
+        assert_eq!(actual, Ok(commands.clone()));
+
+        let mut recorder = Recorder::default();
+        execute(Commands::new(stream.bytes()), &mut recorder).unwrap();
+        assert_eq!(recorder.0, commands);
+
+        let actual: Vec<_> = Commands::new(b"cf\nq\ncf\n".iter().copied()).collect();
+        let expected = [
+            Ok(Command::Clear(Nib::new(0xF).unwrap())),
+            Err(CommandError {
+                line: 2,
+                col: 1,
+                error: ParseError::Unexpected(b'q'),
+            }),
+        ];
+        assert_eq!(actual, expected);
+
+        let actual: Vec<_> = Commands::new(b"i00".iter().copied()).collect();
+        let expected = [Err(CommandError {
+            line: 1,
+            col: 3,
+            error: ParseError::ZeroIndex,
+        })];
+        assert_eq!(actual, expected);
+
+        let actual: Vec<_> = Commands::new(b"i01010108\n".iter().copied()).collect();
+        let expected = [Err(CommandError {
+            line: 1,
+            col: 9,
+            error: ParseError::Unexpected(b'8').within("image.sampling"),
+        })];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn error_position() {
+        let stream = concat!(
+            "cf\n",
+            "t000000000000000000000000000000000000000000000000000\n",
+            "t000000G0000\n",
+        );
+
+        let actual = Commands::new(stream.bytes())
+            .find_map(Result::err)
+            .unwrap()
+            .to_string();
+        let expected = "line 3, col 8: expected hex digit, got 'G' while parsing Pnt.pos.y";
+        assert_eq!(actual, expected);
+
+        let actual = Commands::new(b"cf\np1".iter().copied())
+            .find_map(Result::err)
+            .unwrap()
+            .to_string();
+        let expected = "line 2, col 3: unexpected end of input while parsing Col.r";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn recovery() {
+        let stream = concat!("cf\n", "q\n", "c\n", "c0 garbage\n", "si01\n", "\n",);
+
+        let actual: Vec<_> = Commands::new(stream.bytes()).with_recovery().collect();
+        let expected = [
+            Ok(Command::Clear(Nib::new(0xF).unwrap())),
+            Err(CommandError {
+                line: 2,
+                col: 1,
+                error: ParseError::Unexpected(b'q'),
+            }),
+            Err(CommandError {
+                line: 3,
+                col: 2,
+                error: ParseError::Byte(b'\n').within("clear.idx"),
+            }),
+            Err(CommandError {
+                line: 4,
+                col: 3,
+                error: ParseError::NotNewLine,
+            }),
+            Ok(Command::SetImage(0x01)),
+            Ok(Command::Finish),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            images: 1,
+            memory: 3,
+            ..Limits::default()
+        };
+
+        let actual: Vec<_> = Commands::new(b"i010202".iter().copied())
+            .with_limits(limits)
+            .collect();
+        let expected = [Err(CommandError {
+            line: 1,
+            col: 7,
+            error: ParseError::Limit(4),
+        })];
+        assert_eq!(actual, expected);
+
+        let actual: Vec<_> = Commands::new(b"i010301000\n".iter().copied())
+            .with_limits(limits)
+            .collect();
+        let expected = [Ok(Command::Image(
+            0x01,
+            Img::new([Nib::new(0x0).unwrap(); 3], (3, 1)).unwrap(),
+        ))];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut first = Recorder::default();
+        let mut second = Recorder::default();
+        let mut tee = Tee(&mut first, Tee(&mut second, ()));
+        for command in commands() {
+            tee.command(command);
+        }
+
+        assert_eq!(first.0, commands());
+        assert_eq!(second.0, commands());
+
+        let mut first = Recorder::default();
+        let mut second = Recorder::default();
+        let mut outs: Vec<Box<dyn Output>> = vec![Box::new(&mut first), Box::new(&mut second)];
+        for command in commands() {
+            outs.command(command);
+        }
+
+        drop(outs);
+        assert_eq!(first.0, commands());
+        assert_eq!(second.0, commands());
+
+        // Nothing to pass commands to
+        Vec::<Box<dyn Output>>::new().finish();
+    }
+}
@@ -2,10 +2,19 @@ use crate::hex;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum ParseError {
+    /// Expected a hex digit.
     Byte(u8),
+    /// Unknown command, event or direction.
+    Unexpected(u8),
+    /// A nibble out of range in the binary encoding.
+    Nibble(u8),
     End,
     NotNewLine,
     ZeroIndex,
+    /// An image of the memory in bytes exceeds the limit of the decoder.
+    Limit(usize),
+    /// The error occurred while parsing the named field.
+    In(&'static str, Box<ParseError>),
 }
 
 impl ParseError {
@@ -15,8 +24,33 @@ impl ParseError {
     {
         bytes.next().ok_or(Self::End)
     }
+
+    /// Names the field where the error occurred, keeping the innermost one.
+    pub(crate) fn within(self, field: &'static str) -> Self {
+        match self {
+            Self::In(..) => self,
+            err => Self::In(field, Box::new(err)),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Byte(b) => write!(f, "expected hex digit, got {:?}", *b as char),
+            Self::Unexpected(b) => write!(f, "unexpected {:?}", *b as char),
+            Self::Nibble(b) => write!(f, "nibble out of range: {:#04x}", b),
+            Self::End => write!(f, "unexpected end of input"),
+            Self::NotNewLine => write!(f, "expected new line"),
+            Self::ZeroIndex => write!(f, "zero image index"),
+            Self::Limit(memory) => write!(f, "image of {} bytes exceeds the memory limit", memory),
+            Self::In(field, err) => write!(f, "{} while parsing {}", err, field),
+        }
+    }
 }
 
+impl std::error::Error for ParseError {}
+
 impl From<u8> for ParseError {
     fn from(b: u8) -> Self {
         Self::Byte(b)
@@ -44,6 +78,10 @@ where
 {
     fn parse(bytes: &mut B) -> Result<Self, ParseError> {
         let a = ParseError::next(bytes)?;
+
+        // Check the first digit before reading the next byte,
+        // so the error position points at the wrong one
+        hex::read_u4(a)?;
         let b = ParseError::next(bytes)?;
         hex::read_u8([a, b]).map_err(ParseError::Byte)
     }
@@ -59,3 +97,37 @@ where
         Ok(a << 8 | b)
     }
 }
+
+impl<B> Parse<B> for u32
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let a = u16::parse(bytes)? as u32;
+        let b = u16::parse(bytes)? as u32;
+        Ok(a << 16 | b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let actual = ParseError::Byte(b'G').within("Pnt.pos.y").to_string();
+        let expected = "expected hex digit, got 'G' while parsing Pnt.pos.y";
+        assert_eq!(actual, expected);
+
+        let actual = ParseError::End
+            .within("Col.b")
+            .within("palette")
+            .to_string();
+        let expected = "unexpected end of input while parsing Col.b";
+        assert_eq!(actual, expected);
+
+        let actual = ParseError::Unexpected(b'\n').to_string();
+        let expected = "unexpected '\\n'";
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{hex, Parse, ParseError};
+
+/// Rectangular region of texel indices, updating a part of an image.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Patch {
+    data: Box<[u8]>,
+    pos: (u16, u16),
+    size: (u16, u16),
+}
+
+impl Patch {
+    pub fn new<D>(data: D, pos: (u16, u16), size: (u16, u16)) -> Option<Self>
+    where
+        D: Into<Box<[u8]>>,
+    {
+        let data = data.into();
+        if data.len() != size.0 as usize * size.1 as usize {
+            return None;
+        }
+
+        Some(Self { data, pos, size })
+    }
+
+    /// Texel indices, row by row.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Position of the top left texel.
+    pub fn pos(&self) -> (u16, u16) {
+        self.pos
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Checks whether the patch is within an image of the size
+    /// and its texels index the palette of the image.
+    pub fn fits(&self, size: (u16, u16), indexed: bool) -> bool {
+        let (x, y) = self.pos;
+        let (w, h) = self.size;
+        x as u32 + w as u32 <= size.0 as u32
+            && y as u32 + h as u32 <= size.1 as u32
+            && (indexed || self.data.iter().all(|&idx| idx <= 0x0F))
+    }
+}
+
+impl<B> Parse<B> for Patch
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let x = u16::parse(bytes).map_err(|err| err.within("Patch.x"))?;
+        let y = u16::parse(bytes).map_err(|err| err.within("Patch.y"))?;
+        let w = u16::parse(bytes).map_err(|err| err.within("Patch.w"))?;
+        let h = u16::parse(bytes).map_err(|err| err.within("Patch.h"))?;
+
+        // The size isn't trusted before the data is actually read
+        let len = w as usize * h as usize;
+        let mut data = Vec::with_capacity(len.min(0x10000));
+        for _ in 0..len {
+            let idx = u8::parse(bytes).map_err(|err| err.within("Patch.data"))?;
+            data.push(idx);
+        }
+
+        Ok(Self::new(data, (x, y), (w, h)).unwrap())
+    }
+}
+
+impl std::fmt::Display for Patch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (x, y) = self.pos;
+        let (w, h) = self.size;
+        hex::write_u16(x, f)?;
+        hex::write_u16(y, f)?;
+        hex::write_u16(w, f)?;
+        hex::write_u16(h, f)?;
+        for &idx in self.data.iter() {
+            hex::write_u8(idx, f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let actual = Patch::from_bytes(*b"0001000200020001ff0a");
+        let expected = Ok(Patch::new([0xFF, 0x0A], (1, 2), (2, 1)).unwrap());
+        assert_eq!(actual, expected);
+
+        let actual = Patch::from_bytes(*b"0001000200020001ff");
+        let expected = Err(ParseError::End.within("Patch.data"));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        let actual = Patch::new([0x01, 0xAB], (0x100, 0), (1, 2))
+            .unwrap()
+            .to_string();
+        let expected = "010000000001000201ab";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn fits() {
+        let patch = Patch::new([0x0F; 4], (2, 1), (2, 2)).unwrap();
+        assert!(patch.fits((4, 3), false));
+        assert!(!patch.fits((3, 3), false));
+        assert!(!patch.fits((4, 2), false));
+
+        let patch = Patch::new([0x10], (0, 0), (1, 1)).unwrap();
+        assert!(!patch.fits((1, 1), false));
+        assert!(patch.fits((1, 1), true));
+
+        let patch = Patch::new([], (u16::MAX, u16::MAX), (1, 0)).unwrap();
+        assert!(!patch.fits((u16::MAX, u16::MAX), true));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Nib, Parse, ParseError};
+use crate::{hex, Nib, Parse, ParseError};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Pnt {
@@ -12,12 +12,12 @@ where
     B: Iterator<Item = u8>,
 {
     fn parse(bytes: &mut B) -> Result<Self, ParseError> {
-        let x = u16::parse(bytes)? as i16;
-        let y = u16::parse(bytes)? as i16;
-        let z = u16::parse(bytes)? as i16;
-        let u = u8::parse(bytes)?;
-        let v = u8::parse(bytes)?;
-        let col = Nib::parse(bytes)?;
+        let x = u16::parse(bytes).map_err(|err| err.within("Pnt.pos.x"))? as i16;
+        let y = u16::parse(bytes).map_err(|err| err.within("Pnt.pos.y"))? as i16;
+        let z = u16::parse(bytes).map_err(|err| err.within("Pnt.pos.z"))? as i16;
+        let u = u8::parse(bytes).map_err(|err| err.within("Pnt.tex.u"))?;
+        let v = u8::parse(bytes).map_err(|err| err.within("Pnt.tex.v"))?;
+        let col = Nib::parse(bytes).map_err(|err| err.within("Pnt.col"))?;
 
         Ok(Self {
             pos: [x, y, z],
@@ -27,6 +27,20 @@ where
     }
 }
 
+impl std::fmt::Display for Pnt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for p in self.pos {
+            hex::write_u16(p as u16, f)?;
+        }
+
+        for t in self.tex {
+            hex::write_u8(t, f)?;
+        }
+
+        write!(f, "{}", self.col)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +55,16 @@ mod tests {
         });
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn display() {
+        let actual = Pnt {
+            pos: [0x0011, -1, 0x4455],
+            tex: [0x11, 0x22],
+            col: Nib::new(0x0E).unwrap(),
+        }
+        .to_string();
+        let expected = "0011ffff44551122e";
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,864 @@
+use crate::{
+    output::Output, Blend, Col, Depth, Elems, Img, Limits, Mat, Nib, Patch, Pnt, Tri, Usage, Verts,
+};
+use std::collections::HashMap;
+
+type Color = [f32; 4];
+
+/// Software implementation of [`Output`].
+///
+/// Renders into an in-memory RGB framebuffer, rows from top to bottom.
+pub struct Raster {
+    size: (u32, u32),
+    pixels: Box<[u8]>,
+    depths: Box<[f32]>,
+    depth: Depth,
+    blend: Blend,
+    matrix: [[f32; 4]; 4],
+    palette: [Color; 16],
+    images: HashMap<u8, Img>,
+    active: u8,
+    vertices: HashMap<u8, Verts>,
+    limits: Limits,
+    usage: Usage,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32) -> Self {
+        const BLACK: Color = [0., 0., 0., 1.];
+
+        Self {
+            size: (width, height),
+            pixels: vec![0; width as usize * height as usize * 3].into_boxed_slice(),
+            depths: vec![1.; width as usize * height as usize].into_boxed_slice(),
+            depth: Depth::Off,
+            blend: Blend::Off,
+            matrix: Mat::identity().to_f32(),
+            palette: [BLACK; 16],
+            images: HashMap::default(),
+            active: 0,
+            vertices: HashMap::default(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+        }
+    }
+
+    /// Sets limits of images and vertex buffers, one exceeding them is ignored.
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Col {
+        let (width, _) = self.size;
+        let i = (y as usize * width as usize + x as usize) * 3;
+        Col::new(self.pixels[i], self.pixels[i + 1], self.pixels[i + 2])
+    }
+
+    fn put(&mut self, x: u32, y: u32, [r, g, b, a]: Color) {
+        let (width, _) = self.size;
+        let i = (y as usize * width as usize + x as usize) * 3;
+        for (dst, c) in self.pixels[i..i + 3].iter_mut().zip([r, g, b]) {
+            let old = *dst as f32 / 255.;
+            let new = match self.blend {
+                Blend::Off => c,
+                Blend::Alpha => c * a + old * (1. - a),
+                Blend::Add => c * a + old,
+            };
+
+            *dst = (new.clamp(0., 1.) * 255.).round() as u8;
+        }
+    }
+
+    /// Tests the fragment depth in the window space and updates the stored one if passed.
+    fn test_depth(&mut self, x: u32, y: u32, depth: f32) -> bool {
+        let (width, _) = self.size;
+        let stored = &mut self.depths[y as usize * width as usize + x as usize];
+        let pass = match self.depth {
+            Depth::Off => return true,
+            Depth::Never => false,
+            Depth::Less => depth < *stored,
+            Depth::Equal => depth == *stored,
+            Depth::LessEqual => depth <= *stored,
+            Depth::Greater => depth > *stored,
+            Depth::NotEqual => depth != *stored,
+            Depth::GreaterEqual => depth >= *stored,
+            Depth::Always => true,
+        };
+
+        if pass {
+            *stored = depth;
+        }
+
+        pass
+    }
+
+    fn texel(&self, [u, v]: [f32; 2]) -> Color {
+        // An empty image has no texels to sample, it's drawn as if no image was set
+        let img = match self.images.get(&self.active) {
+            Some(img) if img.size().0 > 0 && img.size().1 > 0 => img,
+            _ => return [1., 1., 1., 1.],
+        };
+
+        let (w, h) = (img.size().0 as i32, img.size().1 as i32);
+        let sampling = img.sampling();
+        let wrap = |c: i32, len: i32| {
+            if sampling.clamp {
+                c.clamp(0, len - 1)
+            } else {
+                c.rem_euclid(len)
+            }
+        };
+
+        let fetch = |x: i32, y: i32| {
+            let idx = img.data()[(wrap(y, h) * w + wrap(x, w)) as usize] as usize;
+            if sampling.transparent && idx == 0 {
+                return [0.; 4];
+            }
+
+            match img.palette() {
+                Some(palette) => {
+                    let Col([r, g, b]) = palette[idx];
+                    [r as f32 / 255., g as f32 / 255., b as f32 / 255., 1.]
+                }
+                None => self.palette[idx],
+            }
+        };
+
+        if !sampling.linear {
+            return fetch((u * w as f32).floor() as i32, (v * h as f32).floor() as i32);
+        }
+
+        // Colors are mixed after the palette lookup, indices aren't interpolated
+        let s = u * w as f32 - 0.5;
+        let t = v * h as f32 - 0.5;
+        let (x, y) = (s.floor() as i32, t.floor() as i32);
+        let (fx, fy) = (s - s.floor(), t - t.floor());
+        let mut color = [0.; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1. - fx) * (1. - fy)),
+            (1, 0, fx * (1. - fy)),
+            (0, 1, (1. - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let texel = fetch(x + dx, y + dy);
+            for (c, t) in color.iter_mut().zip(texel) {
+                *c += t * weight;
+            }
+        }
+
+        color
+    }
+}
+
+/// Vertex in the clip space.
+#[derive(Copy, Clone)]
+struct Vertex {
+    pos: [f32; 4],
+    tex: [f32; 2],
+    col: Color,
+}
+
+impl Vertex {
+    fn new(p: Pnt, palette: &[Color; 16], matrix: &[[f32; 4]; 4]) -> Self {
+        const ADDITION: f32 = 1. / 512.;
+
+        let xp = p.pos[0] as f32 / 256.;
+        let yp = p.pos[1] as f32 / 256.;
+        let zp = p.pos[2] as f32 / 256.;
+        let ut = p.tex[0] as f32 / 256. + ADDITION;
+        let vt = p.tex[1] as f32 / 256. + ADDITION;
+
+        Self {
+            pos: matrix.map(|[a, b, c, d]| a * xp + b * yp + c * zp + d),
+            tex: [ut, vt],
+            col: palette[p.col.get() as usize],
+        }
+    }
+
+    fn mix(&self, other: &Self, t: f32) -> Self {
+        fn mix<const N: usize>(a: [f32; N], b: [f32; N], t: f32) -> [f32; N] {
+            let mut out = a;
+            for (o, b) in out.iter_mut().zip(b) {
+                *o += (b - *o) * t;
+            }
+            out
+        }
+
+        Self {
+            pos: mix(self.pos, other.pos, t),
+            tex: mix(self.tex, other.tex, t),
+            col: mix(self.col, other.col, t),
+        }
+    }
+
+    /// Projects the vertex to the screen. Attributes are divided by `w`
+    /// to be interpolated with the perspective correction.
+    fn project(&self, (width, height): (u32, u32)) -> Projected {
+        let [x, y, z, w] = self.pos;
+        let inv = 1. / w;
+        Projected {
+            pos: [
+                (x * inv + 1.) * 0.5 * width as f32,
+                (1. - y * inv) * 0.5 * height as f32,
+                z * inv,
+                inv,
+            ],
+            tex: self.tex.map(|t| t * inv),
+            col: self.col.map(|c| c * inv),
+        }
+    }
+}
+
+/// Clips the polygon by the plane, keeping vertices with non-negative distance.
+fn clip<F>(poly: &[Vertex], dist: F) -> Vec<Vertex>
+where
+    F: Fn(&[f32; 4]) -> f32,
+{
+    let mut out = Vec::with_capacity(poly.len() + 1);
+    for (i, a) in poly.iter().enumerate() {
+        let b = &poly[(i + 1) % poly.len()];
+        let (da, db) = (dist(&a.pos), dist(&b.pos));
+        if da >= 0. {
+            out.push(*a);
+        }
+
+        if (da >= 0.) != (db >= 0.) {
+            out.push(a.mix(b, da / (da - db)));
+        }
+    }
+    out
+}
+
+/// Vertex in the screen space, `pos` is x, y, z and inverted w.
+struct Projected {
+    pos: [f32; 4],
+    tex: [f32; 2],
+    col: Color,
+}
+
+fn edge(a: &Projected, b: &Projected, [x, y]: [f32; 2]) -> f32 {
+    let [ax, ay, ..] = a.pos;
+    let [bx, by, ..] = b.pos;
+    (bx - ax) * (y - ay) - (by - ay) * (x - ax)
+}
+
+fn is_top_left(a: &Projected, b: &Projected) -> bool {
+    let dx = b.pos[0] - a.pos[0];
+    let dy = b.pos[1] - a.pos[1];
+    dy < 0. || dy == 0. && dx > 0.
+}
+
+fn bounds(vs: [f32; 3], len: u32) -> std::ops::Range<u32> {
+    let min = vs.iter().fold(f32::MAX, |m, &v| m.min(v));
+    let max = vs.iter().fold(f32::MIN, |m, &v| m.max(v));
+    let clamp = |v: f32| v.max(0.).min(len as f32) as u32;
+    clamp(min.floor())..clamp(max.ceil())
+}
+
+fn lerp<const N: usize>(vals: [[f32; N]; 3], [l0, l1, l2]: [f32; 3]) -> [f32; N] {
+    let mut out = [0.; N];
+    for (i, o) in out.iter_mut().enumerate() {
+        *o = vals[0][i] * l0 + vals[1][i] * l1 + vals[2][i] * l2;
+    }
+    out
+}
+
+impl Raster {
+    fn fill(&mut self, [a, mut b, mut c]: [Projected; 3]) {
+        let mut area = edge(&a, &b, [c.pos[0], c.pos[1]]);
+        if area == 0. {
+            return;
+        }
+
+        if area < 0. {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+
+        let (width, height) = self.size;
+        let xs = [a.pos[0], b.pos[0], c.pos[0]];
+        let ys = [a.pos[1], b.pos[1], c.pos[1]];
+        let edges = [(&b, &c), (&c, &a), (&a, &b)];
+        for y in bounds(ys, height) {
+            for x in bounds(xs, width) {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let mut ls = [0.; 3];
+                let inside = edges.iter().zip(&mut ls).all(|(&(q, w), l)| {
+                    *l = edge(q, w, p);
+                    *l > 0. || *l == 0. && is_top_left(q, w)
+                });
+
+                if !inside {
+                    continue;
+                }
+
+                let ls = ls.map(|l| l / area);
+                let [z, inv] = lerp([a.pos, b.pos, c.pos].map(|[_, _, z, w]| [z, w]), ls);
+                if !(-1. ..=1.).contains(&z) {
+                    continue;
+                }
+
+                let tex = lerp([a.tex, b.tex, c.tex], ls).map(|t| t / inv);
+                let [tr, tg, tb, ta] = self.texel(tex);
+                let [r, g, b, a] = lerp([a.col, b.col, c.col], ls).map(|c| c / inv);
+                let color = [r * tr, g * tg, b * tb, a * ta];
+
+                // Transparent fragments are discarded before the depth test
+                if color[3] <= 0. || !self.test_depth(x, y, (z + 1.) * 0.5) {
+                    continue;
+                }
+
+                self.put(x, y, color);
+            }
+        }
+    }
+}
+
+impl Output for Raster {
+    fn palette(&mut self, idx: Nib, Col([r, g, b]): Col) {
+        let [_, _, _, a] = self.palette[idx.get() as usize];
+        self.palette[idx.get() as usize] = [r as f32 / 255., g as f32 / 255., b as f32 / 255., a];
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.palette[idx.get() as usize][3] = alpha as f32 / 255.;
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.blend = blend;
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        let [r, g, b, _] = self.palette[idx.get() as usize];
+        let (width, height) = self.size;
+        let blend = std::mem::replace(&mut self.blend, Blend::Off);
+        for y in 0..height {
+            for x in 0..width {
+                self.put(x, y, [r, g, b, 1.]);
+            }
+        }
+
+        self.blend = blend;
+
+        self.depths.fill(1.);
+    }
+
+    fn draw_triangle(&mut self, Tri(points): Tri) {
+        // Near plane clipping keeps `w` positive for the projection
+        const MIN_W: f32 = 1e-5;
+
+        let poly = points.map(|p| Vertex::new(p, &self.palette, &self.matrix));
+        let poly = clip(&poly, |[_, _, z, w]| z + w);
+        let poly = clip(&poly, |[_, _, z, w]| w - z);
+        let poly = clip(&poly, |[.., w]| w - MIN_W);
+        if poly.len() < 3 {
+            return;
+        }
+
+        let size = self.size;
+        for i in 1..poly.len() - 1 {
+            let tri = [&poly[0], &poly[i], &poly[i + 1]];
+            self.fill(tri.map(|v| v.project(size)));
+        }
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        let replaced = self.vertices.get(&idx).map(Verts::memory);
+        let usage = self.usage.add_vertices(verts.memory(), replaced);
+        if !self.limits.allows(usage) {
+            return;
+        }
+
+        self.usage = usage;
+        self.vertices.insert(idx, verts);
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        let tris: Vec<_> = match self.vertices.get(&idx) {
+            Some(verts) => elems.triangles(verts.points()).collect(),
+            None => return,
+        };
+
+        for tri in tris {
+            self.draw_triangle(tri);
+        }
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        let replaced = self.images.get(&idx).map(Img::memory);
+        let usage = self.usage.add(img.memory(), replaced);
+        if !self.limits.allows(usage) {
+            return;
+        }
+
+        self.usage = usage;
+        self.images.insert(idx, img);
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        if let Some(img) = self.images.get_mut(&idx) {
+            img.update(&patch);
+        }
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        if let Some(img) = self.images.remove(&idx) {
+            self.usage = self.usage.remove(img.memory());
+        }
+
+        if self.active == idx {
+            self.active = 0;
+        }
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        self.active = if self.images.contains_key(&idx) {
+            idx
+        } else {
+            0
+        };
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.depth = depth;
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.matrix = mat.to_f32();
+    }
+
+    fn finish(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Primitive, Sampling};
+
+    fn point(x: i16, y: i16, tex: [u8; 2], col: u8) -> Pnt {
+        Pnt {
+            pos: [x, y, 0],
+            tex,
+            col: Nib::new(col).unwrap(),
+        }
+    }
+
+    fn full_screen(z: i16, col: u8) -> [Tri; 2] {
+        let point = |x, y| Pnt {
+            pos: [x, y, z],
+            tex: [0, 0],
+            col: Nib::new(col).unwrap(),
+        };
+
+        let lt = point(-256, 256);
+        let rt = point(256, 256);
+        let lb = point(-256, -256);
+        let rb = point(256, -256);
+        [Tri([lt, rt, lb]), Tri([rt, rb, lb])]
+    }
+
+    #[test]
+    fn clear() {
+        let mut raster = Raster::new(4, 2);
+        raster.palette(Nib::new(3).unwrap(), Col::new(0x10, 0x20, 0x30));
+        raster.clear(Nib::new(3).unwrap());
+
+        let actual = raster.pixels();
+        let expected = [0x10, 0x20, 0x30].repeat(8);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn draw_triangle() {
+        let mut raster = Raster::new(4, 4);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0x00, 0x00));
+
+        // Covers the bottom-left half of the screen
+        raster.draw_triangle(Tri([
+            point(-256, -256, [0, 0], 1),
+            point(256, -256, [0, 0], 1),
+            point(-256, 256, [0, 0], 1),
+        ]));
+
+        assert_eq!(raster.pixel(0, 3), Col::new(0xFF, 0x00, 0x00));
+        assert_eq!(raster.pixel(1, 2), Col::new(0xFF, 0x00, 0x00));
+        assert_eq!(raster.pixel(2, 1), Col::new(0x00, 0x00, 0x00));
+        assert_eq!(raster.pixel(3, 0), Col::new(0x00, 0x00, 0x00));
+    }
+
+    #[test]
+    fn draw_elements() {
+        let mut raster = Raster::new(4, 4);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0x00, 0x00));
+
+        // The same full screen quad as a list and as a strip
+        let points = [
+            point(-256, 256, [0, 0], 1),
+            point(256, 256, [0, 0], 1),
+            point(-256, -256, [0, 0], 1),
+            point(256, -256, [0, 0], 1),
+        ];
+        raster.vertices(1, Verts::new(points).unwrap());
+        for (primitive, indices) in [
+            (Primitive::List, vec![0, 1, 2, 1, 3, 2]),
+            (Primitive::Strip, vec![0, 1, 2, 3]),
+        ] {
+            raster.clear(Nib::new(0).unwrap());
+            raster.draw_elements(1, Elems::new(primitive, indices).unwrap());
+
+            let red = [0xFF, 0x00, 0x00];
+            assert_eq!(raster.pixels(), red.repeat(16));
+        }
+
+        // Unknown buffers are ignored
+        raster.clear(Nib::new(0).unwrap());
+        raster.draw_elements(2, Elems::new(Primitive::List, [0, 1, 2]).unwrap());
+        assert!(raster.pixels().iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn draw_textured() {
+        let mut raster = Raster::new(2, 2);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0xFF, 0xFF));
+        raster.palette(Nib::new(2).unwrap(), Col::new(0x00, 0xFF, 0x00));
+        raster.palette(Nib::new(3).unwrap(), Col::new(0x00, 0x00, 0xFF));
+
+        let data = [2, 3, 3, 2].map(|n| Nib::new(n).unwrap());
+        raster.image(1, Img::new(data, (2, 2)).unwrap());
+        raster.set_image(1);
+
+        // Two triangles cover the whole screen, texture is mapped once
+        let lt = point(-256, 256, [0, 0], 1);
+        let rt = point(256, 256, [255, 0], 1);
+        let lb = point(-256, -256, [0, 255], 1);
+        let rb = point(256, -256, [255, 255], 1);
+        raster.draw_triangle(Tri([lt, rt, lb]));
+        raster.draw_triangle(Tri([rt, rb, lb]));
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0x00, 0xFF, 0x00));
+        assert_eq!(raster.pixel(1, 0), Col::new(0x00, 0x00, 0xFF));
+        assert_eq!(raster.pixel(0, 1), Col::new(0x00, 0x00, 0xFF));
+        assert_eq!(raster.pixel(1, 1), Col::new(0x00, 0xFF, 0x00));
+    }
+
+    #[test]
+    fn draw_empty_image() {
+        let mut raster = Raster::new(2, 2);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0x00, 0x00));
+
+        // Empty images have no texels, the triangle keeps its color in all sampling modes
+        for size in [(0, 0), (0, 10)] {
+            for mode in 0..8 {
+                let sampling = Sampling::new(mode).unwrap();
+                raster.image(1, Img::new([], size).unwrap().with_sampling(sampling));
+                raster.set_image(1);
+                raster.clear(Nib::new(0).unwrap());
+                raster.draw_triangle(Tri([
+                    point(-256, -256, [0, 0], 1),
+                    point(256, -256, [255, 0], 1),
+                    point(-256, 256, [0, 255], 1),
+                ]));
+
+                assert_eq!(raster.pixel(0, 1), Col::new(0xFF, 0x00, 0x00));
+            }
+        }
+    }
+
+    #[test]
+    fn draw_indexed() {
+        let mut raster = Raster::new(2, 1);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0xFF, 0xFF));
+
+        let mut palette = Box::new([Col::new(0x00, 0x00, 0x00); 256]);
+        palette[0x10] = Col::new(0x12, 0x34, 0x56);
+        palette[0xFF] = Col::new(0xAB, 0xCD, 0xEF);
+        raster.image(1, Img::indexed([0x10, 0xFF], (2, 1), palette).unwrap());
+        raster.set_image(1);
+
+        let lt = point(-256, 256, [0, 0], 1);
+        let rt = point(256, 256, [255, 0], 1);
+        let lb = point(-256, -256, [0, 0], 1);
+        let rb = point(256, -256, [255, 0], 1);
+        raster.draw_triangle(Tri([lt, rt, lb]));
+        raster.draw_triangle(Tri([rt, rb, lb]));
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0x12, 0x34, 0x56));
+        assert_eq!(raster.pixel(1, 0), Col::new(0xAB, 0xCD, 0xEF));
+    }
+
+    #[test]
+    fn limits() {
+        let mut raster = Raster::new(1, 1).with_limits(Limits {
+            images: 2,
+            memory: 8,
+            vertices: 2 * std::mem::size_of::<Pnt>(),
+        });
+
+        let img = |len| Img::extended(vec![Nib::new(0).unwrap(); len], (len as u16, 1)).unwrap();
+        raster.image(1, img(4));
+        raster.image(2, img(4));
+        raster.image(3, img(1));
+        raster.image(2, img(5));
+        let expected = Usage {
+            images: 2,
+            memory: 8,
+            vertices: 0,
+        };
+        assert_eq!(raster.usage(), expected);
+
+        raster.set_image(1);
+        raster.remove_image(1);
+        raster.image(3, img(1));
+        let expected = Usage {
+            images: 2,
+            memory: 5,
+            vertices: 0,
+        };
+        assert_eq!(raster.usage(), expected);
+        assert_eq!(raster.active, 0);
+
+        let p = point(0, 0, [0, 0], 1);
+        raster.vertices(1, Verts::new([p; 2]).unwrap());
+        raster.vertices(2, Verts::new([p]).unwrap());
+        assert!(!raster.vertices.contains_key(&2));
+
+        raster.vertices(1, Verts::new([p]).unwrap());
+        raster.vertices(2, Verts::new([p]).unwrap());
+        assert!(raster.vertices.contains_key(&2));
+        assert_eq!(raster.usage().vertices, 2 * std::mem::size_of::<Pnt>());
+    }
+
+    #[test]
+    fn sampling() {
+        let mut raster = Raster::new(4, 1);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0xFF, 0xFF));
+        raster.palette(Nib::new(2).unwrap(), Col::new(0x00, 0xFF, 0x00));
+        raster.palette(Nib::new(3).unwrap(), Col::new(0x00, 0x00, 0xFF));
+
+        let lt = point(-256, 256, [0, 0], 1);
+        let rt = point(256, 256, [255, 0], 1);
+        let lb = point(-256, -256, [0, 0], 1);
+        let rb = point(256, -256, [255, 0], 1);
+        let data = [2, 3].map(|n| Nib::new(n).unwrap());
+        let img = Img::new(data, (2, 1)).unwrap();
+        let mut draw = |sampling| {
+            raster.image(1, img.clone().with_sampling(sampling));
+            raster.set_image(1);
+            raster.draw_triangle(Tri([lt, rt, lb]));
+            raster.draw_triangle(Tri([rt, rb, lb]));
+            [0, 1, 2, 3].map(|x| raster.pixel(x, 0))
+        };
+
+        // The nearest texel by default
+        let green = Col::new(0x00, 0xFF, 0x00);
+        let blue = Col::new(0x00, 0x00, 0xFF);
+        let actual = draw(Sampling::default());
+        assert_eq!(actual, [green, green, blue, blue]);
+
+        // Edges mix with the opposite side when repeated
+        let actual = draw(Sampling {
+            linear: true,
+            clamp: false,
+            transparent: false,
+        });
+        let expected = [
+            Col::new(0x00, 0xC0, 0x3F),
+            Col::new(0x00, 0xBF, 0x40),
+            Col::new(0x00, 0x40, 0xBF),
+            Col::new(0x00, 0x3F, 0xC0),
+        ];
+        assert_eq!(actual, expected);
+
+        // Clamped edges keep their own color
+        let actual = draw(Sampling {
+            linear: true,
+            clamp: true,
+            transparent: false,
+        });
+        let expected = [green, expected[1], expected[2], blue];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn depth() {
+        let mut raster = Raster::new(2, 2);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0x00, 0x00));
+        raster.palette(Nib::new(2).unwrap(), Col::new(0x00, 0xFF, 0x00));
+        raster.clear(Nib::new(0).unwrap());
+
+        // Without the depth test the last triangle wins
+        let far = full_screen(128, 1);
+        let near = full_screen(-128, 2);
+        for tri in near.iter().chain(&far) {
+            raster.draw_triangle(*tri);
+        }
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0xFF, 0x00, 0x00));
+
+        raster.depth(Depth::Less);
+        raster.clear(Nib::new(0).unwrap());
+        for tri in near.iter().chain(&far) {
+            raster.draw_triangle(*tri);
+        }
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0x00, 0xFF, 0x00));
+
+        raster.depth(Depth::Greater);
+        for tri in far {
+            raster.draw_triangle(tri);
+        }
+
+        assert_eq!(raster.pixel(1, 1), Col::new(0xFF, 0x00, 0x00));
+    }
+
+    #[test]
+    fn matrix() {
+        let mut raster = Raster::new(4, 4);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0x00, 0x00));
+        raster.clear(Nib::new(0).unwrap());
+
+        // The doubled `w` shrinks the screen quad twice
+        raster.matrix(Mat::from_f32([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 2.],
+        ]));
+
+        for tri in full_screen(0, 1) {
+            raster.draw_triangle(tri);
+        }
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0x00, 0x00, 0x00));
+        assert_eq!(raster.pixel(1, 1), Col::new(0xFF, 0x00, 0x00));
+        assert_eq!(raster.pixel(2, 2), Col::new(0xFF, 0x00, 0x00));
+        assert_eq!(raster.pixel(3, 3), Col::new(0x00, 0x00, 0x00));
+
+        // Everything behind the camera is clipped
+        raster.clear(Nib::new(0).unwrap());
+        raster.matrix(Mat::from_f32([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., -1.],
+        ]));
+
+        for tri in full_screen(0, 1) {
+            raster.draw_triangle(tri);
+        }
+
+        assert!(raster.pixels().iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn perspective() {
+        let mut raster = Raster::new(8, 4);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0xFF, 0xFF));
+        raster.palette(Nib::new(2).unwrap(), Col::new(0x00, 0xFF, 0x00));
+        raster.palette(Nib::new(3).unwrap(), Col::new(0x00, 0x00, 0xFF));
+
+        let data = [2, 3].map(|n| Nib::new(n).unwrap());
+        raster.image(1, Img::new(data, (2, 1)).unwrap());
+        raster.set_image(1);
+
+        // The right side is three times further than the left one
+        raster.matrix(Mat::from_f32([
+            [2., 0., 0., 1.],
+            [0., 3., 0., 0.],
+            [0., 0., 1., 0.],
+            [1., 0., 0., 2.],
+        ]));
+
+        let lt = point(-256, 256, [0, 0], 1);
+        let rt = point(256, 256, [255, 0], 1);
+        let lb = point(-256, -256, [0, 0], 1);
+        let rb = point(256, -256, [255, 0], 1);
+        raster.draw_triangle(Tri([lt, rt, lb]));
+        raster.draw_triangle(Tri([rt, rb, lb]));
+
+        // The texture middle is shifted to the far side
+        assert_eq!(raster.pixel(5, 2), Col::new(0x00, 0xFF, 0x00));
+        assert_eq!(raster.pixel(6, 2), Col::new(0x00, 0x00, 0xFF));
+    }
+
+    #[test]
+    fn transparency() {
+        let mut raster = Raster::new(2, 2);
+        raster.palette(Nib::new(1).unwrap(), Col::new(0xFF, 0xFF, 0xFF));
+        raster.palette(Nib::new(2).unwrap(), Col::new(0x00, 0xFF, 0x00));
+        raster.palette(Nib::new(3).unwrap(), Col::new(0xFF, 0x00, 0x00));
+        raster.clear(Nib::new(3).unwrap());
+
+        // The zero index is transparent by the sampling, its entry stays opaque
+        let transparent = Sampling {
+            transparent: true,
+            ..Sampling::default()
+        };
+        let data = [0, 2, 2, 0].map(|n| Nib::new(n).unwrap());
+        raster.image(
+            1,
+            Img::new(data, (2, 2)).unwrap().with_sampling(transparent),
+        );
+        raster.set_image(1);
+
+        let lt = point(-256, 256, [0, 0], 1);
+        let rt = point(256, 256, [255, 0], 1);
+        let lb = point(-256, -256, [0, 255], 1);
+        let rb = point(256, -256, [255, 255], 1);
+        let draw = |raster: &mut Raster| {
+            raster.draw_triangle(Tri([lt, rt, lb]));
+            raster.draw_triangle(Tri([rt, rb, lb]));
+            [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| raster.pixel(x, y))
+        };
+
+        let red = Col::new(0xFF, 0x00, 0x00);
+        let green = Col::new(0x00, 0xFF, 0x00);
+        assert_eq!(draw(&mut raster), [red, green, green, red]);
+
+        // The same for images with an own palette
+        let mut palette = Box::new([Col::new(0x00, 0x00, 0xFF); 256]);
+        palette[7] = green;
+        let img = Img::indexed([0, 7, 7, 0], (2, 2), palette).unwrap();
+        raster.image(2, img.with_sampling(transparent));
+        raster.set_image(2);
+        raster.clear(Nib::new(3).unwrap());
+        assert_eq!(draw(&mut raster), [red, green, green, red]);
+
+        // And without the flag the zero entry is opaque
+        raster.image(1, Img::new(data, (2, 2)).unwrap());
+        raster.set_image(1);
+        raster.clear(Nib::new(3).unwrap());
+        let black = Col::new(0x00, 0x00, 0x00);
+        assert_eq!(draw(&mut raster), [black, green, green, black]);
+
+        raster.clear(Nib::new(3).unwrap());
+        raster.image(
+            1,
+            Img::new(data, (2, 2)).unwrap().with_sampling(transparent),
+        );
+        draw(&mut raster);
+
+        // Translucent overlay
+        raster.set_image(0);
+        raster.alpha(Nib::new(2).unwrap(), 0x80);
+        raster.blend(Blend::Alpha);
+        for tri in full_screen(0, 2) {
+            raster.draw_triangle(tri);
+        }
+
+        assert_eq!(raster.pixel(0, 0), Col::new(0x7F, 0x80, 0x00));
+        assert_eq!(raster.pixel(1, 0), Col::new(0x00, 0xFF, 0x00));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{hex, Parse, ParseError};
+
+/// Sampling of image texels, encoded as one hex digit of flags.
+///
+/// Bit 0 turns on the linear filtering, bit 1 clamps texture coordinates,
+/// bit 2 makes the texel index 0 transparent.
+/// The default is the nearest texel with repeated coordinates, which keeps pixel art sharp.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Sampling {
+    /// Mixes colors of the four nearest texels instead of taking the nearest one.
+    /// Texels are mixed after the palette lookup, so indices are never interpolated.
+    pub linear: bool,
+    /// Clamps texture coordinates to the image edge instead of repeating the image.
+    pub clamp: bool,
+    /// Makes texels of the index 0 transparent, whatever the color of the entry is.
+    /// It applies to both the shared palette and the own one of an image.
+    pub transparent: bool,
+}
+
+impl Sampling {
+    const LINEAR: u8 = 0b001;
+    const CLAMP: u8 = 0b010;
+    const TRANSPARENT: u8 = 0b100;
+
+    pub fn new(mode: u8) -> Option<Self> {
+        if mode & !(Self::LINEAR | Self::CLAMP | Self::TRANSPARENT) != 0 {
+            return None;
+        }
+
+        Some(Self {
+            linear: mode & Self::LINEAR != 0,
+            clamp: mode & Self::CLAMP != 0,
+            transparent: mode & Self::TRANSPARENT != 0,
+        })
+    }
+
+    pub fn get(self) -> u8 {
+        let mut mode = 0;
+        if self.linear {
+            mode |= Self::LINEAR;
+        }
+
+        if self.clamp {
+            mode |= Self::CLAMP;
+        }
+
+        if self.transparent {
+            mode |= Self::TRANSPARENT;
+        }
+
+        mode
+    }
+}
+
+impl<B> Parse<B> for Sampling
+where
+    B: Iterator<Item = u8>,
+{
+    fn parse(bytes: &mut B) -> Result<Self, ParseError> {
+        let byte = ParseError::next(bytes)?;
+        let mode = hex::read_u4(byte)?;
+        Self::new(mode).ok_or(ParseError::Unexpected(byte))
+    }
+}
+
+impl std::fmt::Display for Sampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        hex::write_u4(self.get(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let actual = Sampling::from_bytes(*b"2");
+        let expected = Ok(Sampling {
+            linear: false,
+            clamp: true,
+            transparent: false,
+        });
+        assert_eq!(actual, expected);
+
+        let actual = Sampling::from_bytes(*b"5");
+        let expected = Ok(Sampling {
+            linear: true,
+            clamp: false,
+            transparent: true,
+        });
+        assert_eq!(actual, expected);
+
+        let actual = Sampling::from_bytes(*b"8");
+        let expected = Err(ParseError::Unexpected(b'8'));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn display() {
+        for mode in 0..8 {
+            let actual = Sampling::new(mode).unwrap().to_string();
+            let expected = mode.to_string();
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    output::Output, Blend, Col, Depth, Elems, Img, Mat, Nib, Patch, Primitive, Tri, Verts,
+};
+use std::time::{Duration, Instant};
+
+/// Counts of commands of frames and the time the output spent on them.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Counts {
+    pub frames: usize,
+    pub commands: usize,
+    /// Triangles drawn directly or by elements, including ones skipped for indices out of range.
+    pub triangles: usize,
+    /// Palette entry colors and alphas set.
+    pub palette: usize,
+    /// Images loaded and updated.
+    pub uploads: usize,
+    /// Memory of loaded images, as counted by [`Img::memory`], and texels of updates.
+    pub upload_bytes: usize,
+    pub image_switches: usize,
+    pub time: Duration,
+}
+
+impl std::ops::Add for Counts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            frames: self.frames + other.frames,
+            commands: self.commands + other.commands,
+            triangles: self.triangles + other.triangles,
+            palette: self.palette + other.palette,
+            uploads: self.uploads + other.uploads,
+            upload_bytes: self.upload_bytes + other.upload_bytes,
+            image_switches: self.image_switches + other.image_switches,
+            time: self.time + other.time,
+        }
+    }
+}
+
+impl std::fmt::Display for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} frames, {} commands, {} triangles, {} palette changes, \
+             {} uploads of {} bytes, {} image switches, {:.2?} in output",
+            self.frames,
+            self.commands,
+            self.triangles,
+            self.palette,
+            self.uploads,
+            self.upload_bytes,
+            self.image_switches,
+            self.time,
+        )
+    }
+}
+
+/// Counts commands passed to the output and times it.
+pub struct Stats<O> {
+    out: O,
+    frame: Counts,
+    last: Counts,
+    total: Counts,
+}
+
+impl<O> Stats<O> {
+    pub fn new(out: O) -> Self {
+        Self {
+            out,
+            frame: Counts::default(),
+            last: Counts::default(),
+            total: Counts::default(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &O {
+        &self.out
+    }
+
+    pub fn into_inner(self) -> O {
+        self.out
+    }
+
+    /// Counts of the last finished frame.
+    pub fn last_frame(&self) -> Counts {
+        self.last
+    }
+
+    /// Returns counts of frames finished since the last call.
+    pub fn take_total(&mut self) -> Counts {
+        std::mem::take(&mut self.total)
+    }
+
+    fn timed<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut O),
+    {
+        let start = Instant::now();
+        f(&mut self.out);
+        self.frame.commands += 1;
+        self.frame.time += start.elapsed();
+    }
+}
+
+impl<O> Output for Stats<O>
+where
+    O: Output,
+{
+    fn palette(&mut self, idx: Nib, col: Col) {
+        self.frame.palette += 1;
+        self.timed(|out| out.palette(idx, col))
+    }
+
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.frame.palette += 1;
+        self.timed(|out| out.alpha(idx, alpha))
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.timed(|out| out.blend(blend))
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        self.timed(|out| out.clear(idx))
+    }
+
+    fn draw_triangle(&mut self, tri: Tri) {
+        self.frame.triangles += 1;
+        self.timed(|out| out.draw_triangle(tri))
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        self.timed(|out| out.vertices(idx, verts))
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        let len = elems.indices().len();
+        self.frame.triangles += match elems.primitive() {
+            Primitive::List => len / 3,
+            Primitive::Strip => len.saturating_sub(2),
+        };
+        self.timed(|out| out.draw_elements(idx, elems))
+    }
+
+    fn image(&mut self, idx: u8, img: Img) {
+        self.frame.uploads += 1;
+        self.frame.upload_bytes += img.memory();
+        self.timed(|out| out.image(idx, img))
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        self.frame.uploads += 1;
+        self.frame.upload_bytes += patch.data().len();
+        self.timed(|out| out.update_image(idx, patch))
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.timed(|out| out.remove_image(idx))
+    }
+
+    fn set_image(&mut self, idx: u8) {
+        self.frame.image_switches += 1;
+        self.timed(|out| out.set_image(idx))
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.timed(|out| out.depth(depth))
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.timed(|out| out.matrix(mat))
+    }
+
+    fn finish(&mut self) {
+        self.timed(|out| out.finish());
+        self.frame.frames = 1;
+        self.last = std::mem::take(&mut self.frame);
+        self.total = self.total + self.last;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{output::Command, Pnt};
+
+    #[test]
+    fn counts() {
+        let nib = Nib::new(1).unwrap();
+        let p = Pnt {
+            pos: [0, 0, 0],
+            tex: [0, 0],
+            col: nib,
+        };
+        let mut stats = Stats::new(());
+        let commands = [
+            Command::Palette(nib, Col::new(1, 2, 3)),
+            Command::Alpha(nib, 0x80),
+            Command::Triangle(Tri([p; 3])),
+            Command::Elements(0, Elems::new(Primitive::Strip, [0, 1, 2, 3]).unwrap()),
+            Command::Image(1, Img::new([nib; 4], (2, 2)).unwrap()),
+            Command::UpdateImage(1, Patch::new([0; 2], (0, 0), (2, 1)).unwrap()),
+            Command::SetImage(1),
+            Command::Finish,
+        ];
+        for command in commands {
+            stats.command(command);
+        }
+
+        stats.command(Command::SetImage(0));
+        stats.command(Command::Finish);
+
+        let actual = Counts {
+            time: Duration::default(),
+            ..stats.last_frame()
+        };
+        let expected = Counts {
+            frames: 1,
+            commands: 2,
+            image_switches: 1,
+            ..Counts::default()
+        };
+        assert_eq!(actual, expected);
+
+        let actual = Counts {
+            time: Duration::default(),
+            ..stats.take_total()
+        };
+        let expected = Counts {
+            frames: 2,
+            commands: 10,
+            triangles: 3,
+            palette: 2,
+            uploads: 2,
+            upload_bytes: 6,
+            image_switches: 2,
+            time: Duration::default(),
+        };
+        assert_eq!(actual, expected);
+        assert_eq!(stats.take_total(), Counts::default());
+    }
+}
@@ -16,6 +16,13 @@ where
     }
 }
 
+impl std::fmt::Display for Tri {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let [a, b, c] = self.0;
+        write!(f, "{}{}{}", a, b, c)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,4 +39,16 @@ mod tests {
         let expected = Ok(Tri([point, point, point]));
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn display() {
+        let point = Pnt {
+            pos: [0x0011, 0x2233, 0x4455],
+            tex: [0x66, 0x77],
+            col: Nib::new(0x08).unwrap(),
+        };
+        let actual = Tri([point, point, point]).to_string();
+        let expected = "001122334455667780011223344556677800112233445566778";
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,207 @@
+use crate::transport::Listen;
+use gni::Limits;
+use std::{path::PathBuf, str::FromStr};
+
+const USAGE: &str = "\
+Usage: gni_bin [OPTIONS] [FILE]
+
+Reads commands from FILE, stdin or a client and writes input events to stdout or the client.
+Binary streams are detected by their header.
+
+Options:
+    --listen ADDRESS   Accept a single client on unix:PATH or tcp:HOST:PORT
+    --dump DIR         Render frames without a window and write them to DIR
+    --lint             Check commands for mistakes without rendering and print warnings
+    --format FORMAT    Dumped image format: ppm or png (default: png)
+    --size WxH         Dumped image size (default: 256x256)
+    --stats            Print statistics of commands, timing and memory of held images
+                       every second to stderr
+    --on-eof ACTION    At the end of input: exit or keep the last frame (default: exit).
+                       Exits with status 1 if reading or decoding the input failed at any
+                       point, otherwise 0
+    --max-images N     Maximum number of loaded images (default: 255)
+    --max-image-memory BYTES
+                       Maximum memory of loaded images, a larger image is rejected before
+                       its texels are read (default: 67108864)
+    --max-vertex-memory BYTES
+                       Maximum memory of vertex buffers (default: 16777216)
+    --record FILE      Record input bytes of frames and input actions with their timing to FILE
+    --replay FILE      Replay a recording instead of reading commands. Recorded actions are
+                       sent as events and handled, so a recorded quit action exits
+    --speed FACTOR     Replay speed relative to the recording, 0 for no waiting (default: 1)
+    -h, --help         Print this help";
+
+#[derive(Copy, Clone)]
+pub enum Format {
+    Ppm,
+    Png,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ppm" => Ok(Self::Ppm),
+            "png" => Ok(Self::Png),
+            _ => Err(format!("unknown format {:?}", s)),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum OnEof {
+    Exit,
+    Keep,
+}
+
+impl FromStr for OnEof {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exit" => Ok(Self::Exit),
+            "keep" => Ok(Self::Keep),
+            _ => Err(format!("unknown end of input action {:?}", s)),
+        }
+    }
+}
+
+pub enum Mode {
+    Window,
+    Dump { dir: PathBuf, format: Format },
+    Lint,
+}
+
+pub struct Args {
+    pub mode: Mode,
+    pub input: Option<PathBuf>,
+    pub size: (u32, u32),
+    pub on_eof: OnEof,
+    pub limits: Limits,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub speed: f64,
+    pub stats: bool,
+    pub listen: Option<Listen>,
+}
+
+impl Args {
+    /// Parses the process arguments. Prints the usage and exits on error.
+    pub fn parse() -> Self {
+        match Self::from_iter(std::env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("{}\n\n{}", err, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn from_iter<I>(args: I) -> Result<Option<Self>, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        fn value<I>(args: &mut I, name: &str) -> Result<String, String>
+        where
+            I: Iterator<Item = String>,
+        {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        }
+
+        let mut dump = None;
+        let mut lint = false;
+        let mut format = Format::Png;
+        let mut input = None;
+        let mut size = (256, 256);
+        let mut on_eof = OnEof::Exit;
+        let mut limits = Limits::default();
+        let mut record = None;
+        let mut replay = None;
+        let mut speed = 1.;
+        let mut stats = false;
+        let mut listen = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--dump" => dump = Some(value(&mut args, &arg)?.into()),
+                "--listen" => listen = Some(value(&mut args, &arg)?.parse()?),
+                "--lint" => lint = true,
+                "--stats" => stats = true,
+                "--format" => format = value(&mut args, &arg)?.parse()?,
+                "--size" => size = parse_size(&value(&mut args, &arg)?)?,
+                "--on-eof" => on_eof = value(&mut args, &arg)?.parse()?,
+                "--max-images" => limits.images = parse_number(&value(&mut args, &arg)?)?,
+                "--max-image-memory" => limits.memory = parse_number(&value(&mut args, &arg)?)?,
+                "--max-vertex-memory" => limits.vertices = parse_number(&value(&mut args, &arg)?)?,
+                "--record" => record = Some(value(&mut args, &arg)?.into()),
+                "--replay" => replay = Some(value(&mut args, &arg)?.into()),
+                "--speed" => speed = parse_speed(&value(&mut args, &arg)?)?,
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if input.is_some() => return Err(format!("unexpected argument {}", arg)),
+                _ => input = Some(arg.into()),
+            }
+        }
+
+        if replay.is_some() && input.is_some() {
+            return Err("--replay reads the recording instead of FILE".to_string());
+        }
+
+        if listen.is_some() && (replay.is_some() || input.is_some()) {
+            return Err("--listen reads commands from the client instead of a file".to_string());
+        }
+
+        let mode = match (dump, lint) {
+            (Some(_), true) => return Err("--dump and --lint can't be combined".to_string()),
+            (Some(dir), false) => Mode::Dump { dir, format },
+            (None, true) => Mode::Lint,
+            (None, false) => Mode::Window,
+        };
+        if stats && !matches!(mode, Mode::Window) {
+            return Err("--stats only applies to the window".to_string());
+        }
+
+        Ok(Some(Self {
+            mode,
+            input,
+            size,
+            on_eof,
+            limits,
+            record,
+            replay,
+            speed,
+            stats,
+            listen,
+        }))
+    }
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let err = || format!("invalid size {:?}", s);
+    let (w, h) = s.split_once('x').ok_or_else(err)?;
+    let w = w.parse().map_err(|_| err())?;
+    let h = h.parse().map_err(|_| err())?;
+    if w == 0 || h == 0 {
+        return Err(err());
+    }
+
+    Ok((w, h))
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number {:?}", s))
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(speed) if speed >= 0. && f64::is_finite(speed) => Ok(speed),
+        _ => Err(format!("invalid speed {:?}", s)),
+    }
+}
@@ -0,0 +1,222 @@
+use crate::args::Format;
+use gni::{
+    output::{Command, CommandError, Output},
+    raster::Raster,
+    Limits,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Renders every frame with the software rasterizer and writes it to the directory.
+/// Fails after all the commands if any of them failed to decode.
+pub fn run<I>(
+    commands: I,
+    dir: &Path,
+    format: Format,
+    (width, height): (u32, u32),
+    limits: Limits,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = Result<Command, CommandError>>,
+{
+    std::fs::create_dir_all(dir)?;
+
+    let mut raster = Raster::new(width, height).with_limits(limits);
+    let mut n = 0;
+    let mut failed = 0;
+    for command in commands {
+        match command {
+            Ok(Command::Finish) => {
+                raster.finish();
+                let (name, write): (_, fn(_, _) -> _) = match format {
+                    Format::Ppm => (format!("{:05}.ppm", n), write_ppm),
+                    Format::Png => (format!("{:05}.png", n), write_png),
+                };
+
+                let mut file = BufWriter::new(File::create(dir.join(name))?);
+                write(&mut file, &raster)?;
+                file.flush()?;
+                n += 1;
+            }
+            Ok(command) => raster.command(command),
+            Err(err) => {
+                eprintln!("{}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        let msg = format!("{} commands failed to decode", failed);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+
+    Ok(())
+}
+
+fn write_ppm(w: &mut dyn Write, raster: &Raster) -> io::Result<()> {
+    let (width, height) = raster.size();
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    w.write_all(raster.pixels())
+}
+
+/// Writes an uncompressed PNG.
+fn write_png(w: &mut dyn Write, raster: &Raster) -> io::Result<()> {
+    fn chunk(w: &mut dyn Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        w.write_all(&(data.len() as u32).to_be_bytes())?;
+        w.write_all(kind)?;
+        w.write_all(data)?;
+        let crc = crc32(kind.iter().chain(data));
+        w.write_all(&crc.to_be_bytes())
+    }
+
+    let (width, height) = raster.size();
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+
+    // 8 bit depth, RGB, default compression, filtering and no interlace
+    header.extend([8, 2, 0, 0, 0]);
+
+    // Every scanline is prefixed with the filter type
+    let row = width as usize * 3;
+    let mut raw = Vec::with_capacity((row + 1) * height as usize);
+    for line in raster.pixels().chunks(row) {
+        raw.push(0);
+        raw.extend(line);
+    }
+
+    // Zlib stream of stored deflate blocks
+    const BLOCK: usize = u16::MAX as usize;
+    let mut data = Vec::with_capacity(raw.len() + raw.len() / BLOCK * 5 + 11);
+    data.extend([0x78, 0x01]);
+    let mut blocks = raw.chunks(BLOCK).peekable();
+    if blocks.peek().is_none() {
+        data.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+        data.push(last);
+        data.extend(len.to_le_bytes());
+        data.extend((!len).to_le_bytes());
+        data.extend(block);
+    }
+
+    data.extend(adler32(&raw).to_be_bytes());
+
+    w.write_all(b"\x89PNG\r\n\x1a\n")?;
+    chunk(w, b"IHDR", &header)?;
+    chunk(w, b"IDAT", &data)?;
+    chunk(w, b"IEND", &[])
+}
+
+fn crc32<'a, I>(bytes: I) -> u32
+where
+    I: IntoIterator<Item = &'a u8>,
+{
+    let mut crc = !0;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = crc >> 1 ^ 0xEDB8_8320 & mask;
+        }
+    }
+
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+
+    let (mut a, mut b) = (1, 0);
+    for &byte in bytes {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    /// Two pixels of the color 0a141e.
+    fn raster() -> Raster {
+        let nib = gni::Nib::new(1).unwrap();
+        let mut raster = Raster::new(2, 1);
+        raster.palette(nib, gni::Col::new(0x0A, 0x14, 0x1E));
+        raster.clear(nib);
+        raster
+    }
+
+    #[test]
+    fn decode_errors() {
+        let dir = std::env::temp_dir().join(format!("gni-dump-{}", std::process::id()));
+        let stream = b"c1\n\nq\nc1\n\n";
+        let commands = gni::output::Commands::new(stream.iter().copied()).with_recovery();
+        let actual = run(commands, &dir, Format::Ppm, (1, 1), Limits::default())
+            .unwrap_err()
+            .to_string();
+
+        // Frames around the error are still written
+        let frames = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(actual, "1 commands failed to decode");
+        assert_eq!(frames, 2);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut actual = Vec::new();
+        write_ppm(&mut actual, &raster()).unwrap();
+        let expected = b"P6\n2 1\n255\n\x0a\x14\x1e\x0a\x14\x1e";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn png() {
+        let mut png = Vec::new();
+        write_png(&mut png, &raster()).unwrap();
+
+        let (signature, mut rest) = png.split_at(8);
+        assert_eq!(signature, b"\x89PNG\r\n\x1a\n");
+
+        // Splits chunks checking their CRC
+        let mut chunks = Vec::new();
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc, crc32(kind.iter().chain(data)));
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+
+        let kinds: Vec<_> = chunks.iter().map(|&(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+
+        let ihdr = [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+        assert_eq!(chunks[0].1, ihdr);
+
+        // A zlib header, a single final stored block and the Adler-32 of the scanlines
+        let raw = [0, 0x0A, 0x14, 0x1E, 0x0A, 0x14, 0x1E];
+        let mut idat = vec![0x78, 0x01, 1, 7, 0, !7, 0xFF];
+        idat.extend(raw);
+        idat.extend(adler32(&raw).to_be_bytes());
+        assert_eq!(chunks[1].1, idat);
+    }
+}
@@ -1,5 +1,18 @@
+use gni::input::Action;
+
+pub enum Frame {
+    /// Nothing new to show.
+    Skip,
+    /// The frame is drawn and should be presented.
+    Present,
+    /// Close the window and exit with the status code.
+    Exit(i32),
+}
+
 pub trait Event {
     fn resize(&mut self, size: (u32, u32));
 
-    fn draw(&mut self);
+    fn action(&mut self, action: Action);
+
+    fn draw(&mut self) -> Frame;
 }
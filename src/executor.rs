@@ -1,58 +1,157 @@
-use crate::render::{Render, Vertex};
-use gni::{output::Output, Col, Img, Nib, Pnt, Tri};
+use gni::{
+    output::Output, Blend, Col, Depth, Elems, Img, Limits, Mat, Nib, Patch, Pnt, Tri, Usage, Verts,
+};
+use gni_bin::render::{Backend, Render, Vertex};
+use std::collections::{HashMap, HashSet};
 
-pub struct Executor {
-    render: Render,
+/// Executes commands with the renderer.
+///
+/// Triangles are queued and drawn in batches. Queued triangles are drawn before every state change,
+/// so each triangle is drawn with the state at the time it was submitted.
+pub struct Executor<R = Render> {
+    render: R,
+    vertices: HashMap<u8, Verts>,
+    limits: Limits,
+    /// Memory of vertex buffers, the renderer counts images.
+    vertex_memory: usize,
+    /// Indices of unloaded images already reported, so a frame using them again stays quiet.
+    unloaded: HashSet<u8>,
 }
 
-impl Executor {
-    pub fn new(render: Render) -> Self {
-        Self { render }
+impl<R> Executor<R>
+where
+    R: Backend,
+{
+    pub fn new(render: R) -> Self {
+        Self {
+            render,
+            vertices: HashMap::default(),
+            limits: Limits::default(),
+            vertex_memory: 0,
+            unloaded: HashSet::default(),
+        }
+    }
+
+    /// Sets limits of vertex buffers, a buffer exceeding them is ignored.
+    /// Limits of images are set by the renderer.
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Images held by the renderer and vertex buffers.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            vertices: self.vertex_memory,
+            ..self.render.image_usage()
+        }
     }
 
     fn palette(&mut self, idx: Nib, Col([r, g, b]): Col) {
         let qc = r as f32 / 255.;
         let wc = g as f32 / 255.;
         let ec = b as f32 / 255.;
+        self.render.draw_buffer();
         self.render.set_color(idx.get(), [qc, wc, ec]);
         self.render.check_error();
     }
 
-    fn clear(&self, idx: Nib) {
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        self.render.draw_buffer();
+        self.render.set_alpha(idx.get(), alpha as f32 / 255.);
+        self.render.check_error();
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        self.render.draw_buffer();
+        self.render.set_blend(blend);
+        self.render.check_error();
+    }
+
+    fn clear(&mut self, idx: Nib) {
+        self.render.draw_buffer();
         self.render.clear(idx.get());
         self.render.check_error();
     }
 
-    fn draw_triangle(&mut self, Tri([a, b, c]): Tri) {
-        fn vertex(p: Pnt) -> Vertex {
-            const ADDITION: f32 = 1. / 512.;
+    fn draw_triangle(&mut self, tri: Tri) {
+        self.render.add_to_buffer(vertices(tri));
+    }
+
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        let replaced = self.vertices.get(&idx).map(Verts::memory);
+        let usage = self.usage().add_vertices(verts.memory(), replaced);
+        if !self.limits.allows(usage) {
+            eprintln!(
+                "vertex buffer {:02x} of {} points and {} bytes exceeds the limits, {} are used",
+                idx,
+                verts.points().len(),
+                verts.memory(),
+                self.usage(),
+            );
+            return;
+        }
 
-            let xp = p.pos[0] as f32 / 256.;
-            let yp = p.pos[1] as f32 / 256.;
-            let zp = p.pos[2] as f32 / 256.;
-            let ut = p.tex[0] as f32 / 256. + ADDITION;
-            let vt = p.tex[1] as f32 / 256. + ADDITION;
-            let col = p.col.get() as u32;
+        self.vertex_memory = usage.vertices;
+        self.vertices.insert(idx, verts);
+    }
 
-            Vertex {
-                pos: [xp, yp, zp],
-                tex: [ut, vt],
-                col,
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        if let Some(verts) = self.vertices.get(&idx) {
+            for tri in elems.triangles(verts.points()) {
+                self.render.add_to_buffer(vertices(tri));
             }
         }
-
-        let qv = vertex(a);
-        let wv = vertex(b);
-        let ev = vertex(c);
-        self.render.add_to_buffer([qv, wv, ev]);
     }
 
     fn image(&mut self, idx: u8, img: Img) {
-        self.render.add_image(idx, &img)
+        self.render.draw_buffer();
+        if self.render.add_image(idx, &img) {
+            self.unloaded.remove(&idx);
+        } else {
+            let usage = self.usage();
+            let (width, height) = img.size();
+            eprintln!(
+                "image {:02x} of {}x{} texels and {} bytes exceeds the limits, {} are used",
+                idx,
+                width,
+                height,
+                img.memory(),
+                usage,
+            );
+        }
+
+        self.render.check_error();
+    }
+
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        self.render.draw_buffer();
+        self.render.update_image(idx, &patch);
+        self.render.check_error();
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.render.draw_buffer();
+        self.render.remove_image(idx);
+        self.render.check_error();
     }
 
     fn set_image(&mut self, idx: u8) {
-        self.render.set_image(idx)
+        self.render.draw_buffer();
+        if !self.render.set_image(idx) && idx != 0 && self.unloaded.insert(idx) {
+            eprintln!("image {:02x} isn't loaded, drawing without a texture", idx);
+        }
+    }
+
+    fn depth(&mut self, depth: Depth) {
+        self.render.draw_buffer();
+        self.render.set_depth(depth);
+        self.render.check_error();
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        self.render.draw_buffer();
+        self.render.set_matrix(mat);
+        self.render.check_error();
     }
 
     fn finish(&mut self) {
@@ -61,11 +160,43 @@ impl Executor {
     }
 }
 
-impl Output for Executor {
+fn vertices(Tri([a, b, c]): Tri) -> [Vertex; 3] {
+    fn vertex(p: Pnt) -> Vertex {
+        const ADDITION: f32 = 1. / 512.;
+
+        let xp = p.pos[0] as f32 / 256.;
+        let yp = p.pos[1] as f32 / 256.;
+        let zp = p.pos[2] as f32 / 256.;
+        let ut = p.tex[0] as f32 / 256. + ADDITION;
+        let vt = p.tex[1] as f32 / 256. + ADDITION;
+        let col = p.col.get() as u32;
+
+        Vertex {
+            pos: [xp, yp, zp],
+            tex: [ut, vt],
+            col,
+        }
+    }
+
+    [vertex(a), vertex(b), vertex(c)]
+}
+
+impl<R> Output for Executor<R>
+where
+    R: Backend,
+{
     fn palette(&mut self, idx: Nib, col: Col) {
         Self::palette(self, idx, col)
     }
 
+    fn alpha(&mut self, idx: Nib, alpha: u8) {
+        Self::alpha(self, idx, alpha)
+    }
+
+    fn blend(&mut self, blend: Blend) {
+        Self::blend(self, blend)
+    }
+
     fn clear(&mut self, idx: Nib) {
         Self::clear(self, idx)
     }
@@ -74,15 +205,234 @@ impl Output for Executor {
         Self::draw_triangle(self, tri)
     }
 
+    fn vertices(&mut self, idx: u8, verts: Verts) {
+        Self::vertices(self, idx, verts)
+    }
+
+    fn draw_elements(&mut self, idx: u8, elems: Elems) {
+        Self::draw_elements(self, idx, elems)
+    }
+
     fn image(&mut self, idx: u8, img: Img) {
         Self::image(self, idx, img)
     }
 
+    fn update_image(&mut self, idx: u8, patch: Patch) {
+        Self::update_image(self, idx, patch)
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        Self::remove_image(self, idx)
+    }
+
     fn set_image(&mut self, idx: u8) {
         Self::set_image(self, idx)
     }
 
+    fn depth(&mut self, depth: Depth) {
+        Self::depth(self, depth)
+    }
+
+    fn matrix(&mut self, mat: Mat) {
+        Self::matrix(self, mat)
+    }
+
     fn finish(&mut self) {
         Self::finish(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gni::{output::Command, Primitive, Usage};
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Queue,
+        Draw,
+        State(&'static str),
+    }
+
+    /// Records calls instead of rendering.
+    #[derive(Default)]
+    struct Mock {
+        calls: Vec<Call>,
+        queued: usize,
+        images: HashSet<u8>,
+    }
+
+    impl Mock {
+        fn state(&mut self, name: &'static str) {
+            self.calls.push(Call::State(name))
+        }
+    }
+
+    impl Backend for Mock {
+        fn clear(&self, _: u8) {}
+
+        fn add_to_buffer(&mut self, _: [Vertex; 3]) {
+            self.queued += 1;
+            self.calls.push(Call::Queue)
+        }
+
+        fn draw_buffer(&mut self) {
+            if self.queued > 0 {
+                self.queued = 0;
+                self.calls.push(Call::Draw)
+            }
+        }
+
+        fn add_image(&mut self, idx: u8, _: &Img) -> bool {
+            self.state("image");
+            self.images.insert(idx);
+            true
+        }
+
+        fn update_image(&mut self, _: u8, _: &Patch) {
+            self.state("update_image")
+        }
+
+        fn remove_image(&mut self, idx: u8) {
+            self.state("remove_image");
+            self.images.remove(&idx);
+        }
+
+        fn set_image(&mut self, idx: u8) -> bool {
+            self.state("set_image");
+            self.images.contains(&idx)
+        }
+
+        fn image_usage(&self) -> Usage {
+            Usage::default()
+        }
+
+        fn set_depth(&mut self, _: Depth) {
+            self.state("depth")
+        }
+
+        fn set_matrix(&mut self, _: Mat) {
+            self.state("matrix")
+        }
+
+        fn set_color(&mut self, _: u8, _: [f32; 3]) {
+            self.state("color")
+        }
+
+        fn set_alpha(&mut self, _: u8, _: f32) {
+            self.state("alpha")
+        }
+
+        fn set_blend(&mut self, _: Blend) {
+            self.state("blend")
+        }
+
+        fn check_error(&self) {}
+    }
+
+    fn triangle() -> Tri {
+        let p = Pnt {
+            pos: [0, 0, 0],
+            tex: [0, 0],
+            col: Nib::new(1).unwrap(),
+        };
+
+        Tri([p; 3])
+    }
+
+    #[test]
+    fn batching() {
+        let mut exe = Executor::new(Mock::default());
+        exe.command(Command::Triangle(triangle()));
+        exe.command(Command::Triangle(triangle()));
+        exe.command(Command::Finish);
+
+        let expected = [Call::Queue, Call::Queue, Call::Draw];
+        assert_eq!(exe.render.calls, expected);
+
+        // Indexed triangles are queued as well
+        let mut exe = Executor::new(Mock::default());
+        let Tri(points) = triangle();
+        exe.command(Command::Vertices(1, Verts::new(points).unwrap()));
+        exe.command(Command::Elements(
+            1,
+            Elems::new(Primitive::Strip, [0, 1, 2, 0]).unwrap(),
+        ));
+        exe.command(Command::Finish);
+
+        let expected = [Call::Queue, Call::Queue, Call::Draw];
+        assert_eq!(exe.render.calls, expected);
+    }
+
+    #[test]
+    fn vertex_limits() {
+        let Tri(points) = triangle();
+        let memory = Verts::new(points).unwrap().memory();
+        let limits = Limits {
+            vertices: memory,
+            ..Limits::default()
+        };
+
+        let mut exe = Executor::new(Mock::default()).with_limits(limits);
+        exe.command(Command::Vertices(1, Verts::new(points).unwrap()));
+        exe.command(Command::Vertices(2, Verts::new(points).unwrap()));
+        exe.command(Command::Vertices(1, Verts::new(&points[..1]).unwrap()));
+        assert!(!exe.vertices.contains_key(&2));
+        assert_eq!(exe.usage().vertices, memory / 3);
+    }
+
+    #[test]
+    fn unloaded_images() {
+        let img = Img::new([Nib::new(1).unwrap()], (1, 1)).unwrap();
+        let mut exe = Executor::new(Mock::default());
+        exe.command(Command::SetImage(1));
+        exe.command(Command::SetImage(2));
+        exe.command(Command::SetImage(1));
+        assert_eq!(exe.unloaded, HashSet::from([1, 2]));
+
+        // A loaded image is reported again once it's removed
+        exe.command(Command::Image(1, img));
+        exe.command(Command::SetImage(1));
+        assert_eq!(exe.unloaded, HashSet::from([2]));
+        exe.command(Command::RemoveImage(1));
+        exe.command(Command::SetImage(1));
+        assert_eq!(exe.unloaded, HashSet::from([1, 2]));
+    }
+
+    #[test]
+    fn state_changes() {
+        let nib = Nib::new(1).unwrap();
+        let img = Img::new([nib], (1, 1)).unwrap();
+        let commands = [
+            (Command::Palette(nib, Col::new(1, 2, 3)), "color"),
+            (Command::Alpha(nib, 0x80), "alpha"),
+            (Command::Blend(Blend::Alpha), "blend"),
+            (Command::Image(1, img), "image"),
+            (
+                Command::UpdateImage(1, Patch::new([0], (0, 0), (1, 1)).unwrap()),
+                "update_image",
+            ),
+            (Command::SetImage(1), "set_image"),
+            (Command::RemoveImage(1), "remove_image"),
+            (Command::Depth(Depth::Less), "depth"),
+            (Command::Matrix(Mat::identity()), "matrix"),
+        ];
+
+        // Triangles queued before a state change are drawn with the previous state
+        for (command, name) in commands {
+            let mut exe = Executor::new(Mock::default());
+            exe.command(Command::Triangle(triangle()));
+            exe.command(command);
+            exe.command(Command::Triangle(triangle()));
+
+            let expected = [Call::Queue, Call::Draw, Call::State(name), Call::Queue];
+            assert_eq!(exe.render.calls, expected);
+        }
+
+        // Clearing is ordered with triangles too
+        let mut exe = Executor::new(Mock::default());
+        exe.command(Command::Triangle(triangle()));
+        exe.command(Command::Clear(nib));
+        assert_eq!(exe.render.calls, [Call::Queue, Call::Draw]);
+    }
+}
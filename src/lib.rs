@@ -0,0 +1,7 @@
+//! Window and renderer of the binary, a library so benchmarks can use them.
+
+pub mod event;
+pub mod render;
+pub mod window;
+
+pub use window::Window;
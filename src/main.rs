@@ -1,44 +1,293 @@
-mod event;
+mod args;
+mod dump;
 mod executor;
-mod render;
-mod window;
+mod reader;
+mod record;
+mod stats;
+mod transport;
 
-use event::Event;
+use args::{Args, Mode, OnEof};
 use executor::Executor;
-use gni::output::parse_command;
-use render::Render;
-use window::Window;
+use gni::{
+    binary,
+    input::{Action, Resize},
+    lint::Lint,
+    output::{Command, CommandError, Commands, Output},
+    stats::Stats,
+    Limits,
+};
+use gni_bin::{
+    event::{Event, Frame},
+    render::{Backend, Render},
+    Window,
+};
+use reader::{Failure, Poll, Reader};
+use record::{Recorder, Replay, Tap, Tee};
+use stats::{Report, Timed, TimedRead, Timer};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+};
+use transport::{Events, Listener};
+
+/// The executor, counting commands only for the report of `--stats`.
+enum Exe {
+    Plain(Box<Executor>),
+    Counted(Box<Stats<Executor>>, Report),
+}
+
+impl Exe {
+    fn command(&mut self, command: Command) {
+        match self {
+            Exe::Plain(exe) => exe.command(command),
+            Exe::Counted(exe, _) => exe.command(command),
+        }
+    }
+}
 
 struct App {
-    exe: Executor,
+    exe: Exe,
+    reader: Reader,
+    events: Events,
+    on_eof: OnEof,
+    recorder: Option<Arc<Recorder>>,
+    /// Actions of the replayed recording.
+    replayed: Option<Receiver<Action>>,
+    quit: bool,
+}
+
+type Decoder = Box<dyn Iterator<Item = Result<Command, CommandError>> + Send>;
+
+impl App {
+    /// Sends the input event to the client.
+    fn send<E>(&mut self, event: E)
+    where
+        E: std::fmt::Display,
+    {
+        self.events.send(event);
+    }
 }
 
 impl Event for App {
     fn resize(&mut self, (width, height): (u32, u32)) {
-        println!("{}, {}", width, height);
+        let clamp = |n: u32| n.min(u16::MAX as u32) as u16;
+        self.send(Resize(clamp(width), clamp(height)));
+    }
+
+    fn action(&mut self, action: Action) {
+        self.send(action);
+        if let Some(recorder) = &self.recorder {
+            if let Err(err) = recorder.action(action) {
+                eprintln!("recording failed: {}", err);
+            }
+        }
+
+        if action == Action::Quit {
+            self.quit = true;
+        }
+    }
+
+    fn draw(&mut self) -> Frame {
+        let replayed: Vec<_> = self.replayed.iter().flat_map(Receiver::try_iter).collect();
+        for action in replayed {
+            self.action(action);
+        }
+
+        if self.quit {
+            return Frame::Exit(0);
+        }
+
+        let frame = match self.reader.poll() {
+            Poll::Frame(frame) => {
+                for command in frame {
+                    self.exe.command(command);
+                }
+
+                Frame::Present
+            }
+            Poll::Pending => {
+                if let Exe::Counted(_, report) = &mut self.exe {
+                    report.skip();
+                }
+
+                Frame::Skip
+            }
+            Poll::End { failed } => match self.on_eof {
+                OnEof::Exit => Frame::Exit(failed as i32),
+                OnEof::Keep => Frame::Skip,
+            },
+        };
+
+        if let Exe::Counted(exe, report) = &mut self.exe {
+            report.tick(|| (exe.take_total(), exe.get_ref().usage()));
+        }
+
+        frame
+    }
+}
+
+/// Reads the input as a byte stream, timing the reading.
+/// Reading stops at the first error, setting the `failure`.
+fn input(
+    read: Box<dyn Read + Send>,
+    timer: Timer,
+    failure: Failure,
+) -> Box<dyn Iterator<Item = u8> + Send> {
+    let read = TimedRead::new(read, timer);
+    let bytes = BufReader::new(read).bytes().map_while(move |byte| {
+        byte.map_err(|err| {
+            eprintln!("reading failed: {}", err);
+            failure.set();
+        })
+        .ok()
+    });
+    Box::new(bytes)
+}
+
+/// Opens the source of commands and the destination of events.
+fn connect(args: &Args) -> std::io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>)> {
+    if let Some(listen) = &args.listen {
+        let listener = Listener::bind(listen)?;
+        eprintln!("waiting for a client on {}", listener);
+        let conn = listener.accept()?;
+        return Ok((conn.read, conn.write));
     }
 
-    fn draw(&mut self) {
-        let mut line = String::new();
-        loop {
-            line.clear();
-            std::io::stdin().read_line(&mut line).unwrap();
+    let read: Box<dyn Read + Send> = match args.replay.as_ref().or(args.input.as_ref()) {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(std::io::stdin()),
+    };
+
+    Ok((read, Box::new(std::io::stdout())))
+}
+
+/// Chooses the text or binary decoder by the stream header.
+fn decoder(bytes: Box<dyn Iterator<Item = u8> + Send>, limits: Limits) -> Decoder {
+    let mut bytes = bytes.peekable();
+    match binary::read_header(&mut bytes) {
+        Ok(true) => Box::new(binary::Commands::new(bytes).with_limits(limits)),
+        Ok(false) => Box::new(Commands::new(bytes).with_recovery().with_limits(limits)),
+        Err(error) => Box::new(std::iter::once(Err(CommandError {
+            line: 1,
+            col: 1,
+            error,
+        }))),
+    }
+}
 
-            let mut input = line.bytes();
-            match parse_command(&mut input, &mut self.exe) {
-                Ok(true) => (),
-                Ok(false) => return,
-                Err(err) => panic!("{:?}", err),
+/// Prints warnings of the commands. Returns `false` if there are any warnings or errors.
+fn lint(commands: Decoder) -> bool {
+    let mut lint = Lint::new(());
+    let mut clean = true;
+    for command in commands {
+        match command {
+            Ok(command) => lint.command(command),
+            Err(err) => {
+                eprintln!("{}", err);
+                lint.skip();
+                clean = false;
             }
         }
+
+        for warning in lint.take_warnings() {
+            println!("{}", warning);
+            clean = false;
+        }
     }
+
+    clean
 }
 
 fn main() {
-    let window = Window::new("gni");
-    let render = Render::new(&window);
-    render.check_error();
+    let args = Args::parse();
+    let (read, events) = connect(&args).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+
+    // Replayed bytes are read at their recorded time, so waiting for them counts as reading
+    let (read, replayed): (Box<dyn Read + Send>, _) = match args.replay {
+        Some(_) => {
+            let (send, replayed) = mpsc::channel();
+            let replay = Replay::new(BufReader::new(read), args.speed, send);
+            (Box::new(replay), Some(replayed))
+        }
+        None => (read, None),
+    };
+
+    let recorder = args.record.as_deref().map(|path| {
+        let recorder = Recorder::create(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        Arc::new(recorder)
+    });
+
+    let read_timer = Timer::default();
+    let failure = Failure::default();
+    let mut bytes = input(read, read_timer.clone(), failure.clone());
+    if let Some(recorder) = &recorder {
+        bytes = Box::new(Tap::new(bytes, recorder.clone()));
+    }
 
-    let exe = Executor::new(render);
-    window.run(App { exe }, 60);
+    let mut commands = decoder(bytes, args.limits);
+    if let Some(recorder) = &recorder {
+        commands = Box::new(Tee::new(commands, recorder.clone()));
+    }
+
+    let decode = Timer::default();
+    let report = args.stats.then(|| Report::new(read_timer, decode.clone()));
+    if report.is_some() {
+        commands = Box::new(Timed::new(commands, decode));
+    }
+
+    match args.mode {
+        Mode::Window => {
+            let window = Window::new("gni");
+            let render = Render::new(&window, args.limits);
+            render.check_error();
+
+            let exe = Executor::new(render).with_limits(args.limits);
+            let exe = match report {
+                Some(report) => Exe::Counted(Box::new(Stats::new(exe)), report),
+                None => Exe::Plain(Box::new(exe)),
+            };
+            let reader = Reader::spawn(commands, failure.clone());
+            let app = App {
+                exe,
+                reader,
+                events: Events::spawn(events),
+                on_eof: args.on_eof,
+                recorder,
+                replayed,
+                quit: false,
+            };
+
+            // Closing the window or quitting still fails after an error of the input
+            let status = window.run(app, 60);
+            if status != 0 {
+                std::process::exit(status);
+            }
+        }
+        Mode::Dump { dir, format } => {
+            if let Err(err) = dump::run(commands, &dir, format, args.size, args.limits) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        Mode::Lint => {
+            if !lint(commands) {
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Reading may fail after all commands succeeded
+    if failure.get() {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,134 @@
+use gni::output::{Command, CommandError};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+/// Maximum number of complete frames waiting to be presented.
+const QUEUE_LEN: usize = 4;
+
+/// Tells whether reading or decoding of the input failed at any point, even if it recovered.
+#[derive(Clone, Default)]
+pub struct Failure(Arc<AtomicBool>);
+
+impl Failure {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+pub enum Poll {
+    Frame(Vec<Command>),
+    Pending,
+    /// The input is over, an incomplete last frame is dropped.
+    /// `failed` tells whether reading or decoding failed at any point.
+    End {
+        failed: bool,
+    },
+}
+
+/// Reads and parses commands on a dedicated thread, queuing complete frames.
+pub struct Reader {
+    frames: Receiver<Vec<Command>>,
+    failure: Failure,
+}
+
+impl Reader {
+    /// Spawns the thread, setting the `failure` at errors of the commands.
+    pub fn spawn<I>(commands: I, failure: Failure) -> Self
+    where
+        I: IntoIterator<Item = Result<Command, CommandError>> + Send + 'static,
+    {
+        let (send, frames) = mpsc::sync_channel(QUEUE_LEN);
+        let failed = failure.clone();
+        thread::spawn(move || {
+            let mut frame = Vec::new();
+            for command in commands {
+                match command {
+                    Ok(Command::Finish) => {
+                        frame.push(Command::Finish);
+                        if send.send(std::mem::take(&mut frame)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(command) => frame.push(command),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        failed.set();
+                    }
+                }
+            }
+        });
+
+        Self { frames, failure }
+    }
+
+    /// Returns the next complete frame without blocking.
+    pub fn poll(&self) -> Poll {
+        match self.frames.try_recv() {
+            Ok(frame) => Poll::Frame(frame),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::End {
+                failed: self.failure.get(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gni::{Nib, ParseError};
+
+    /// Polls until the end of the input, returning the frames and whether it failed.
+    fn read_all(reader: &Reader) -> (Vec<Vec<Command>>, bool) {
+        let mut frames = Vec::new();
+        loop {
+            match reader.poll() {
+                Poll::Frame(frame) => frames.push(frame),
+                Poll::Pending => thread::yield_now(),
+                Poll::End { failed } => return (frames, failed),
+            }
+        }
+    }
+
+    #[test]
+    fn failure() {
+        let clear = Command::Clear(Nib::new(1).unwrap());
+        let frame = vec![clear.clone(), Command::Finish];
+        let error = CommandError {
+            line: 3,
+            col: 1,
+            error: ParseError::Unexpected(b'q'),
+        };
+
+        let commands = vec![Ok(clear.clone()), Ok(Command::Finish)];
+        let reader = Reader::spawn(commands, Failure::default());
+        assert_eq!(read_all(&reader), (vec![frame.clone()], false));
+
+        // The input recovers from the error in the middle, it still fails
+        let commands = vec![
+            Ok(clear.clone()),
+            Ok(Command::Finish),
+            Err(error),
+            Ok(clear),
+            Ok(Command::Finish),
+        ];
+        let reader = Reader::spawn(commands, Failure::default());
+        assert_eq!(read_all(&reader), (vec![frame.clone(), frame], true));
+
+        // Errors of reading fail as well
+        let failure = Failure::default();
+        failure.set();
+        let reader = Reader::spawn(Vec::new(), failure);
+        assert_eq!(read_all(&reader), (Vec::new(), true));
+    }
+}
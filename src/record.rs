@@ -0,0 +1,322 @@
+//! Recording of sessions with their timing, and their replay.
+//!
+//! A recording holds the input bytes exactly as they were read, in entries for every frame
+//! and input action. An entry starts with a line of its kind and the time since the start
+//! of the session in microseconds:
+//!
+//! ```text
+//! f 16000 4
+//! c0
+//!
+//! a 20500 aa
+//! p 21000 2
+//! q
+//! ```
+//!
+//! A frame `f` is followed by the number of its bytes and the bytes, up to the end of the command
+//! finishing it. A partial frame `p` holds bytes read up to an error or the end of the input.
+//! An action is written on the line of its entry, as it was printed.
+
+use gni::{
+    input::Action,
+    output::{Command, CommandError},
+    Parse,
+};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufWriter, Read, Write},
+    path::Path,
+    str::FromStr,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Writes frames and actions with the time they occurred.
+///
+/// Shared by the reader thread, recording frames, and the window, recording actions.
+pub struct Recorder<W = BufWriter<File>> {
+    out: Mutex<W>,
+    /// Bytes read since the last recorded frame.
+    pending: Mutex<Vec<u8>>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W> Recorder<W>
+where
+    W: Write,
+{
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+            pending: Mutex::new(Vec::new()),
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes the bytes read since the last frame, as a frame if `finished`, otherwise as
+    /// a partial one. Nothing is written for a partial frame without bytes.
+    pub fn frame(&self, finished: bool) -> io::Result<()> {
+        let bytes = std::mem::take(&mut *self.pending.lock().unwrap());
+        if bytes.is_empty() && !finished {
+            return Ok(());
+        }
+
+        let time = self.start.elapsed().as_micros();
+        let kind = if finished { 'f' } else { 'p' };
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{} {} {}", kind, time, bytes.len())?;
+        out.write_all(&bytes)?;
+        out.flush()
+    }
+
+    pub fn action(&self, action: Action) -> io::Result<()> {
+        let time = self.start.elapsed().as_micros();
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "a {} {}", time, action)?;
+        out.flush()
+    }
+}
+
+/// Passes input bytes through, keeping them until a [`Tee`] records their frame.
+pub struct Tap<B, W = BufWriter<File>> {
+    bytes: B,
+    recorder: Arc<Recorder<W>>,
+}
+
+impl<B, W> Tap<B, W> {
+    pub fn new(bytes: B, recorder: Arc<Recorder<W>>) -> Self {
+        Self { bytes, recorder }
+    }
+}
+
+impl<B, W> Iterator for Tap<B, W>
+where
+    B: Iterator<Item = u8>,
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let byte = self.bytes.next()?;
+        self.recorder.pending.lock().unwrap().push(byte);
+        Some(byte)
+    }
+}
+
+/// Passes commands decoded from a [`Tap`] through, recording their bytes at the end of every
+/// frame, at errors and at the end of the input.
+pub struct Tee<I, W = BufWriter<File>> {
+    commands: I,
+    recorder: Arc<Recorder<W>>,
+}
+
+impl<I, W> Tee<I, W> {
+    pub fn new(commands: I, recorder: Arc<Recorder<W>>) -> Self {
+        Self { commands, recorder }
+    }
+}
+
+impl<I, W> Iterator for Tee<I, W>
+where
+    I: Iterator<Item = Result<Command, CommandError>>,
+    W: Write,
+{
+    type Item = Result<Command, CommandError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let command = self.commands.next();
+        let recorded = match &command {
+            Some(Ok(Command::Finish)) => self.recorder.frame(true),
+            Some(Ok(_)) => Ok(()),
+            Some(Err(_)) | None => self.recorder.frame(false),
+        };
+
+        if let Err(err) = recorded {
+            eprintln!("recording failed: {}", err);
+        }
+
+        command
+    }
+}
+
+/// Input bytes of a recording, returning every frame at its recorded time.
+///
+/// Recorded actions are sent at their time, so they're handled as in the session.
+pub struct Replay<R> {
+    recording: R,
+    /// Number of the last read entry, starting from 1.
+    entry: usize,
+    start: Instant,
+    speed: f64,
+    frame: Vec<u8>,
+    /// Position of the next byte of the frame.
+    pos: usize,
+    actions: Sender<Action>,
+}
+
+impl<R> Replay<R>
+where
+    R: BufRead,
+{
+    /// Replays at the speed relative to the original. At the speed 0 entries are not waited for.
+    pub fn new(recording: R, speed: f64, actions: Sender<Action>) -> Self {
+        Self {
+            recording,
+            entry: 0,
+            start: Instant::now(),
+            speed,
+            frame: Vec::new(),
+            pos: 0,
+            actions,
+        }
+    }
+
+    fn wait(&self, time: Duration) {
+        if self.speed > 0. {
+            let at = self.start + time.div_f64(self.speed);
+            let now = Instant::now();
+            if at > now {
+                thread::sleep(at - now);
+            }
+        }
+    }
+
+    /// Reads the next entry. Returns `false` at the end of the recording.
+    fn entry(&mut self) -> io::Result<bool> {
+        let mut line = Vec::new();
+        if self.recording.read_until(b'\n', &mut line)? == 0 {
+            return Ok(false);
+        }
+
+        self.entry += 1;
+        let entry = self.entry;
+        let invalid = |field| {
+            let msg = format!("invalid {} of the recording entry {}", field, entry);
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        };
+
+        if line.pop() != Some(b'\n') {
+            return Err(invalid("end"));
+        }
+
+        let mut fields = line.splitn(3, |&b| b == b' ');
+        let kind = fields.next().unwrap_or_default();
+        let time = number(fields.next()).ok_or_else(|| invalid("time"))?;
+        let rest = fields.next().unwrap_or_default();
+        match kind {
+            b"f" | b"p" => {
+                let len = number(Some(rest)).ok_or_else(|| invalid("length"))?;
+                self.frame.resize(len, 0);
+                self.recording.read_exact(&mut self.frame)?;
+                self.pos = 0;
+                self.wait(Duration::from_micros(time));
+            }
+            b"a" => {
+                let action =
+                    Action::from_bytes(rest.iter().copied()).map_err(|_| invalid("action"))?;
+                self.wait(Duration::from_micros(time));
+
+                // Nothing handles actions outside the window
+                let _ = self.actions.send(action);
+            }
+            _ => return Err(invalid("kind")),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R> Read for Replay<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.frame.len() {
+            if !self.entry()? {
+                return Ok(0);
+            }
+        }
+
+        let read = (&self.frame[self.pos..]).read(buf)?;
+        self.pos += read;
+        Ok(read)
+    }
+}
+
+/// Parses a decimal field of an entry.
+fn number<T>(field: Option<&[u8]>) -> Option<T>
+where
+    T: FromStr,
+{
+    std::str::from_utf8(field?).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gni::{output::Commands, Nib, ParseError};
+    use std::sync::mpsc;
+
+    #[test]
+    fn round_trip() {
+        let recorder = Arc::new(Recorder::new(Vec::new()));
+        let stream = b"c1\n\nq\nc1\n";
+        let bytes = Tap::new(stream.iter().copied(), recorder.clone());
+        let tee = Tee::new(Commands::new(bytes).with_recovery(), recorder.clone());
+        recorder.action(Action::A).unwrap();
+        let clear = Command::Clear(Nib::new(1).unwrap());
+        let actual: Vec<_> = tee.collect();
+        let expected = [
+            Ok(clear.clone()),
+            Ok(Command::Finish),
+            Err(CommandError {
+                line: 3,
+                col: 1,
+                error: ParseError::Unexpected(b'q'),
+            }),
+            Ok(clear),
+        ];
+        assert_eq!(actual, expected);
+
+        // The error and the incomplete last frame are recorded as well
+        let out = Arc::try_unwrap(recorder)
+            .ok()
+            .unwrap()
+            .out
+            .into_inner()
+            .unwrap();
+        let (send, actions) = mpsc::channel();
+        let mut actual = Vec::new();
+        Replay::new(&out[..], 0., send)
+            .read_to_end(&mut actual)
+            .unwrap();
+        assert_eq!(actual, stream);
+        assert_eq!(actions.try_iter().collect::<Vec<_>>(), [Action::A]);
+    }
+
+    #[test]
+    fn replay() {
+        let recording = *b"a 10 aq\nf 20 4\nc1\n\np 30 2\nc\n";
+        let (send, actions) = mpsc::channel();
+        let mut actual = Vec::new();
+        Replay::new(&recording[..], 0., send)
+            .read_to_end(&mut actual)
+            .unwrap();
+        assert_eq!(actual, b"c1\n\nc\n");
+        assert_eq!(actions.try_iter().collect::<Vec<_>>(), [Action::Quit]);
+
+        let (send, _) = mpsc::channel();
+        let actual = Replay::new(&b"f 1x 0\n"[..], 0., send)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err()
+            .to_string();
+        let expected = "invalid time of the recording entry 1";
+        assert_eq!(actual, expected);
+    }
+}
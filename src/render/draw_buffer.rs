@@ -1,5 +1,5 @@
 use glow::{Context, HasContext, NativeBuffer, NativeVertexArray};
-use std::rc::Rc;
+use std::{convert::TryFrom, rc::Rc};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -9,29 +9,26 @@ pub struct Vertex {
     pub col: u32,
 }
 
+/// Triangles queued for drawing.
+///
+/// The vertices grow up to [`DrawBuffer::MAX_CAPACITY`], so a frame is usually drawn with
+/// a single draw call, and are drawn when they reach it.
+/// The GL buffer grows to fit them and is orphaned on every upload,
+/// so the driver doesn't wait for the previous draw call.
 pub struct DrawBuffer {
     context: Rc<Context>,
-    buffer: Box<[Vertex]>,
-    len: usize,
+    vertices: Vec<Vertex>,
+    /// Size of the GL buffer in vertices.
+    capacity: usize,
     nat: (NativeVertexArray, NativeBuffer),
 }
 
 impl DrawBuffer {
-    pub fn new(context: Rc<Context>) -> Self {
-        const ZERO: Vertex = Vertex {
-            pos: [0., 0., 0.],
-            tex: [0., 0.],
-            col: 0,
-        };
+    const MIN_CAPACITY: usize = 3 << 7;
+    /// 64Ki triangles, 4.5 MiB of vertices.
+    const MAX_CAPACITY: usize = 3 << 16;
 
-        let n_triangles = if cfg!(debug_assertions) {
-            1 << 4
-        } else {
-            1 << 7
-        };
-
-        let buffer_len = n_triangles * 3;
-        let buffer = vec![ZERO; buffer_len];
+    pub fn new(context: Rc<Context>) -> Self {
         let nat = unsafe {
             let array = context
                 .create_vertex_array()
@@ -42,11 +39,6 @@ impl DrawBuffer {
             let size = std::mem::size_of::<f32>() as i32;
             let buffer = context.create_buffer().expect("Cannot create buffer");
             context.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
-            context.buffer_data_size(
-                glow::ARRAY_BUFFER,
-                buffer_len as i32 * vertex_size,
-                glow::STREAM_DRAW,
-            );
 
             let attributes = [(0, 3, glow::FLOAT, 0), (1, 2, glow::FLOAT, 3 * size)];
             for (loc, size, data_type, offset) in attributes {
@@ -63,48 +55,54 @@ impl DrawBuffer {
 
         Self {
             context,
-            buffer: buffer.into_boxed_slice(),
-            len: 0,
+            vertices: Vec::with_capacity(Self::MIN_CAPACITY),
+            capacity: 0,
             nat,
         }
     }
 
-    pub fn add(&mut self, triangle: [Vertex; 3]) -> bool {
-        let len = self.len + 3;
-        if len > self.buffer.len() {
-            false
-        } else {
-            self.buffer[self.len..len].copy_from_slice(&triangle);
-            self.len = len;
-            true
+    pub fn add(&mut self, triangle: [Vertex; 3]) {
+        if self.vertices.len() + triangle.len() > Self::MAX_CAPACITY {
+            self.draw();
+            self.clear();
         }
+
+        self.vertices.extend_from_slice(&triangle);
     }
 
-    pub fn draw(&self) {
-        if self.len == 0 {
+    pub fn draw(&mut self) {
+        let len = self.vertices.len();
+        if len == 0 {
             return;
         }
 
         unsafe {
             let (array, buffer) = self.nat;
             self.context.bind_vertex_array(Some(array));
+            self.context.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
 
-            let slice = &self.buffer[..self.len];
-            let src = std::slice::from_raw_parts(
-                slice.as_ptr().cast(),
-                slice.len() * std::mem::size_of::<Vertex>(),
-            );
+            // Reallocating the storage orphans the old one still used by the previous draw call
+            self.capacity = self
+                .capacity
+                .max(len.next_power_of_two())
+                .clamp(Self::MIN_CAPACITY, Self::MAX_CAPACITY);
+            let vertex_size = std::mem::size_of::<Vertex>();
+            let size = i32::try_from(self.capacity * vertex_size).expect("Draw buffer too large");
+            self.context
+                .buffer_data_size(glow::ARRAY_BUFFER, size, glow::STREAM_DRAW);
 
-            self.context.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            let slice = &self.vertices[..];
+            let src =
+                std::slice::from_raw_parts(slice.as_ptr().cast(), std::mem::size_of_val(slice));
             self.context
                 .buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, src);
-            self.context
-                .draw_arrays(glow::TRIANGLES, 0, self.len as i32);
+            let count = i32::try_from(len).expect("Draw buffer too large");
+            self.context.draw_arrays(glow::TRIANGLES, 0, count);
         }
     }
 
     pub fn clear(&mut self) {
-        self.len = 0;
+        self.vertices.clear();
     }
 }
 
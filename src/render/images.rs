@@ -1,110 +1,251 @@
 use crate::render::shader_program::Program;
 use glow::{Context, HasContext, NativeTexture, NativeUniformLocation};
-use std::{
-    collections::{hash_map::Entry, HashMap},
-    rc::Rc,
-};
+use gni::{Img, Limits, Patch, Usage};
+use std::{collections::HashMap, rc::Rc};
+
+struct Texture {
+    tex: NativeTexture,
+    /// The own palette of an indexed image, as a 256x1 texture.
+    palette: Option<NativeTexture>,
+    linear: bool,
+    transparent: bool,
+    size: (u16, u16),
+    memory: usize,
+}
 
 pub struct Images {
     context: Rc<Context>,
-    map: HashMap<u8, NativeTexture>,
+    map: HashMap<u8, Texture>,
     use_tex_loc: NativeUniformLocation,
+    linear_tex_loc: NativeUniformLocation,
+    transparent_tex_loc: NativeUniformLocation,
+    own_palette_loc: NativeUniformLocation,
     active: u8,
+    limits: Limits,
+    usage: Usage,
+    /// Largest width and height of a texture the driver supports.
+    max_size: u32,
 }
 
 impl Images {
-    pub fn new(context: Rc<Context>, program: &Program) -> Self {
+    pub fn new(context: Rc<Context>, program: &Program, limits: Limits) -> Self {
         let use_tex_loc = program.use_tex_loc();
+        let linear_tex_loc = program.linear_tex_loc();
+        let transparent_tex_loc = program.transparent_tex_loc();
+        let own_palette_loc = program.own_palette_loc();
         unsafe {
             context.uniform_1_i32(Some(&program.tex_loc()), 0);
+            context.uniform_1_i32(Some(&program.tex_palette_loc()), 1);
             context.active_texture(glow::TEXTURE0);
             context.uniform_1_u32(Some(&use_tex_loc), 0);
         }
 
+        let max_size = unsafe { context.get_parameter_i32(glow::MAX_TEXTURE_SIZE) };
+
         Self {
             context,
             map: HashMap::default(),
             use_tex_loc,
+            linear_tex_loc,
+            transparent_tex_loc,
+            own_palette_loc,
             active: 0,
+            limits,
+            usage: Usage::default(),
+            max_size: max_size.max(0) as u32,
         }
     }
 
-    pub fn add(&mut self, idx: u8, size: (u8, u8), data: &[u8]) {
-        let tex = unsafe {
-            assert_ne!(idx, 0);
-            assert_eq!(size.0 as usize * size.1 as usize, data.len());
+    /// Adds or replaces the image. Returns `false` if it exceeds the limits
+    /// or the texture size supported by the driver.
+    pub fn add(&mut self, idx: u8, img: &Img) -> bool {
+        let (width, height) = img.size();
+        if width as u32 > self.max_size || height as u32 > self.max_size {
+            return false;
+        }
+
+        let replaced = self.map.get(&idx).map(|texture| texture.memory);
+        let usage = self.usage.add(img.memory(), replaced);
+        if !self.limits.allows(usage) {
+            return false;
+        }
+
+        // Texels are palette indices, so the hardware filtering is always the nearest one
+        // and the linear sampling is done by the shader after the palette lookup
+        let sampling = img.sampling();
+        let wrap = if sampling.clamp {
+            glow::CLAMP_TO_EDGE
+        } else {
+            glow::REPEAT
+        };
+
+        assert_ne!(idx, 0);
+        let tex = self.create_texture(img.size(), wrap, glow::R8UI, glow::RED_INTEGER, img.data());
+
+        let palette = img.palette().map(|palette| {
+            let data: Vec<_> = palette.iter().flat_map(|col| col.0).collect();
+            self.create_texture((256, 1), glow::CLAMP_TO_EDGE, glow::RGB8, glow::RGB, &data)
+        });
+
+        let texture = Texture {
+            tex,
+            palette,
+            linear: sampling.linear,
+            transparent: sampling.transparent,
+            size: img.size(),
+            memory: img.memory(),
+        };
+
+        self.usage = usage;
+        if let Some(old) = self.map.insert(idx, texture) {
+            self.delete_texture(&old);
+        }
+
+        // Creating the texture has bound it, so the active one is restored
+        self.bind(self.active);
+        true
+    }
+
+    pub fn remove(&mut self, idx: u8) {
+        if let Some(texture) = self.map.remove(&idx) {
+            self.usage = self.usage.remove(texture.memory);
+            self.delete_texture(&texture);
+        }
+
+        if self.active == idx {
+            self.bind(0);
+        }
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// Updates a region of the image, ignoring patches that don't fit it.
+    pub fn update(&mut self, idx: u8, patch: &Patch) {
+        let texture = match self.map.get(&idx) {
+            Some(texture) if patch.fits(texture.size, texture.palette.is_some()) => texture,
+            _ => return,
+        };
+
+        let (x, y) = patch.pos();
+        let (width, height) = patch.size();
+        unsafe {
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(texture.tex));
+            self.context.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.context.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                glow::RED_INTEGER,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(patch.data()),
+            );
+        }
+
+        self.bind(self.active);
+    }
 
+    /// Binds the image for drawing. Returns `false` and draws without a texture
+    /// if there is no such image.
+    pub fn bind(&mut self, idx: u8) -> bool {
+        let use_tex = if let Some(texture) = self.map.get(&idx) {
+            self.active = idx;
+            self.bind_texture(texture);
+            1
+        } else {
+            self.active = 0;
+            0
+        };
+
+        unsafe {
+            self.context.uniform_1_u32(Some(&self.use_tex_loc), use_tex);
+        }
+
+        use_tex == 1
+    }
+
+    fn create_texture(
+        &self,
+        (width, height): (u16, u16),
+        wrap: u32,
+        internal_format: u32,
+        format: u32,
+        data: &[u8],
+    ) -> NativeTexture {
+        unsafe {
             let tex = self
                 .context
                 .create_texture()
                 .expect("Cannot create texture");
             self.context.bind_texture(glow::TEXTURE_2D, Some(tex));
-            self.context.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MIN_FILTER,
-                glow::LINEAR as i32,
-            );
-            self.context.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MAG_FILTER,
-                glow::LINEAR as i32,
-            );
+            for (param, value) in [
+                (glow::TEXTURE_MIN_FILTER, glow::NEAREST),
+                (glow::TEXTURE_MAG_FILTER, glow::NEAREST),
+                (glow::TEXTURE_WRAP_S, wrap),
+                (glow::TEXTURE_WRAP_T, wrap),
+            ] {
+                self.context
+                    .tex_parameter_i32(glow::TEXTURE_2D, param, value as i32);
+            }
 
-            let (width, height) = size;
+            // Rows are tightly packed
+            self.context.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
             self.context.tex_image_2d(
                 glow::TEXTURE_2D,
                 0,
-                glow::R8 as i32,
+                internal_format as i32,
                 width as i32,
                 height as i32,
                 0,
-                glow::RED,
+                format,
                 glow::UNSIGNED_BYTE,
                 Some(data),
             );
 
             tex
-        };
-
-        match self.map.entry(idx) {
-            Entry::Occupied(mut en) => unsafe {
-                let old = en.insert(tex);
-                if self.active == idx {
-                    self.bind_texture(tex);
-                }
-
-                self.context.delete_texture(old);
-            },
-            Entry::Vacant(en) => {
-                en.insert(tex);
-            }
         }
     }
 
-    pub fn bind(&mut self, idx: u8) {
-        let use_tex = if let Some(tex) = self.map.get(&idx) {
-            self.active = idx;
-            self.bind_texture(*tex);
-            1
-        } else {
-            self.active = 0;
-            0
-        };
-
+    fn bind_texture(&self, texture: &Texture) {
         unsafe {
-            self.context.uniform_1_u32(Some(&self.use_tex_loc), use_tex);
+            if let Some(palette) = texture.palette {
+                self.context.active_texture(glow::TEXTURE1);
+                self.context.bind_texture(glow::TEXTURE_2D, Some(palette));
+                self.context.active_texture(glow::TEXTURE0);
+            }
+
+            self.context
+                .bind_texture(glow::TEXTURE_2D, Some(texture.tex));
+            self.context
+                .uniform_1_u32(Some(&self.linear_tex_loc), texture.linear as u32);
+            self.context
+                .uniform_1_u32(Some(&self.transparent_tex_loc), texture.transparent as u32);
+            self.context.uniform_1_u32(
+                Some(&self.own_palette_loc),
+                texture.palette.is_some() as u32,
+            );
         }
     }
 
-    fn bind_texture(&self, tex: NativeTexture) {
-        unsafe { self.context.bind_texture(glow::TEXTURE_2D, Some(tex)) }
+    fn delete_texture(&self, texture: &Texture) {
+        unsafe {
+            self.context.delete_texture(texture.tex);
+            if let Some(palette) = texture.palette {
+                self.context.delete_texture(palette);
+            }
+        }
     }
 }
 
 impl Drop for Images {
     fn drop(&mut self) {
-        for tex in self.map.values() {
-            unsafe { self.context.delete_texture(*tex) }
+        for texture in self.map.values() {
+            self.delete_texture(texture);
         }
     }
 }
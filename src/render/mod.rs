@@ -5,8 +5,8 @@ mod shader_program;
 
 use crate::Window;
 use draw_buffer::DrawBuffer;
-use glow::{Context, HasContext};
-use gni::Img;
+use glow::{Context, HasContext, NativeUniformLocation};
+use gni::{Blend, Depth, Img, Limits, Mat, Patch, Usage};
 use images::Images;
 use palette::Palette;
 use shader_program::Program;
@@ -20,74 +20,178 @@ pub struct Render {
     buffer: DrawBuffer,
     images: Images,
     palette: Palette,
+    transform_loc: NativeUniformLocation,
 }
 
 impl Render {
-    pub fn new(window: &Window) -> Self {
+    pub fn new(window: &Window, limits: Limits) -> Self {
         let context = unsafe {
             Context::from_loader_function(|s| window.context().get_proc_address(s).cast()).into()
         };
 
         let program = Program::new(Rc::clone(&context));
         let buffer = DrawBuffer::new(Rc::clone(&context));
-        let images = Images::new(Rc::clone(&context), &program);
+        let images = Images::new(Rc::clone(&context), &program, limits);
         let palette = Palette::new(Rc::clone(&context), program.palette_loc());
         palette.set_uniform();
 
-        Self {
+        let transform_loc = program.transform_loc();
+        let mut render = Self {
             context,
             _program: program,
             buffer,
             images,
             palette,
-        }
+            transform_loc,
+        };
+
+        render.set_matrix(Mat::identity());
+        render
     }
+}
+
+/// Rendering operations used by the executor.
+///
+/// State changes apply immediately, while triangles are queued until the buffer is drawn,
+/// so the caller draws the buffer before changing the state used by queued triangles.
+pub trait Backend {
+    fn clear(&self, idx: u8);
+
+    fn add_to_buffer(&mut self, triangle: [Vertex; 3]);
+
+    fn draw_buffer(&mut self);
+
+    /// Adds or replaces the image. Returns `false` if it exceeds the limits
+    /// or the largest texture size.
+    fn add_image(&mut self, idx: u8, img: &Img) -> bool;
+
+    fn update_image(&mut self, idx: u8, patch: &Patch);
+
+    fn remove_image(&mut self, idx: u8);
+
+    /// Returns `false` if there is no such image, then triangles are drawn without a texture.
+    fn set_image(&mut self, idx: u8) -> bool;
+
+    fn image_usage(&self) -> Usage;
+
+    fn set_depth(&mut self, depth: Depth);
 
-    pub fn clear(&self, idx: u8) {
-        let [r, g, b] = self.palette.colors()[idx as usize];
+    fn set_matrix(&mut self, mat: Mat);
+
+    fn set_color(&mut self, idx: u8, color: [f32; 3]);
+
+    fn set_alpha(&mut self, idx: u8, alpha: f32);
+
+    fn set_blend(&mut self, blend: Blend);
+
+    fn check_error(&self);
+}
+
+impl Backend for Render {
+    fn clear(&self, idx: u8) {
+        let [r, g, b, _] = self.palette.colors()[idx as usize];
         unsafe {
             let mask = glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT;
-            self.context.clear(mask);
             self.context.clear_color(r, g, b, 1.);
+            self.context.clear(mask);
         }
     }
 
-    pub fn add_to_buffer(&mut self, triangle: [Vertex; 3]) {
-        if self.buffer.add(triangle) {
-            return;
-        }
-
-        self.draw_buffer();
-        assert!(self.buffer.add(triangle));
+    fn add_to_buffer(&mut self, triangle: [Vertex; 3]) {
+        self.buffer.add(triangle)
     }
 
-    pub fn draw_buffer(&mut self) {
+    fn draw_buffer(&mut self) {
         self.buffer.draw();
         self.buffer.clear();
     }
 
-    pub fn add_image(&mut self, idx: u8, img: &Img) {
+    fn add_image(&mut self, idx: u8, img: &Img) -> bool {
+        self.images.add(idx, img)
+    }
+
+    fn update_image(&mut self, idx: u8, patch: &Patch) {
+        self.images.update(idx, patch)
+    }
+
+    fn remove_image(&mut self, idx: u8) {
+        self.images.remove(idx)
+    }
+
+    fn set_image(&mut self, idx: u8) -> bool {
+        self.images.bind(idx)
+    }
+
+    fn image_usage(&self) -> Usage {
+        self.images.usage()
+    }
+
+    fn set_depth(&mut self, depth: Depth) {
+        let func = match depth {
+            Depth::Off => None,
+            Depth::Never => Some(glow::NEVER),
+            Depth::Less => Some(glow::LESS),
+            Depth::Equal => Some(glow::EQUAL),
+            Depth::LessEqual => Some(glow::LEQUAL),
+            Depth::Greater => Some(glow::GREATER),
+            Depth::NotEqual => Some(glow::NOTEQUAL),
+            Depth::GreaterEqual => Some(glow::GEQUAL),
+            Depth::Always => Some(glow::ALWAYS),
+        };
+
         unsafe {
-            let data = img.data();
-            self.images.add(
-                idx,
-                img.size(),
-                std::slice::from_raw_parts(data.as_ptr().cast(), data.len()),
-            );
+            match func {
+                Some(func) => {
+                    self.context.enable(glow::DEPTH_TEST);
+                    self.context.depth_func(func);
+                }
+                None => self.context.disable(glow::DEPTH_TEST),
+            }
         }
     }
 
-    pub fn set_image(&mut self, idx: u8) {
-        self.images.bind(idx)
+    fn set_matrix(&mut self, mat: Mat) {
+        unsafe {
+            let data: Vec<_> = mat.to_f32().iter().flatten().copied().collect();
+
+            // The matrix is sent row by row, so it's transposed to the column major order
+            self.context
+                .uniform_matrix_4_f32_slice(Some(&self.transform_loc), true, &data);
+        }
+    }
+
+    fn set_color(&mut self, idx: u8, [r, g, b]: [f32; 3]) {
+        let colors = self.palette.colors_mut();
+        let [_, _, _, a] = colors[idx as usize];
+        colors[idx as usize] = [r, g, b, a];
+        self.palette.set_uniform();
     }
 
-    pub fn set_color(&mut self, idx: u8, color: [f32; 3]) {
+    fn set_alpha(&mut self, idx: u8, alpha: f32) {
         let colors = self.palette.colors_mut();
-        colors[idx as usize] = color;
+        colors[idx as usize][3] = alpha;
         self.palette.set_uniform();
     }
 
-    pub fn check_error(&self) {
+    fn set_blend(&mut self, blend: Blend) {
+        let func = match blend {
+            Blend::Off => None,
+            Blend::Alpha => Some((glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA)),
+            Blend::Add => Some((glow::SRC_ALPHA, glow::ONE)),
+        };
+
+        unsafe {
+            match func {
+                Some((src, dst)) => {
+                    self.context.enable(glow::BLEND);
+                    self.context.blend_func(src, dst);
+                }
+                None => self.context.disable(glow::BLEND),
+            }
+        }
+    }
+
+    fn check_error(&self) {
         let err = unsafe { self.context.get_error() };
         let msg = match err {
             glow::NO_ERROR => return,
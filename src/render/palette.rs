@@ -1,7 +1,7 @@
 use glow::{Context, HasContext, NativeUniformLocation};
 use std::rc::Rc;
 
-type Color = [f32; 3];
+type Color = [f32; 4];
 
 pub struct Palette {
     context: Rc<Context>,
@@ -13,7 +13,7 @@ impl Palette {
     const SIZE: usize = 16;
 
     pub fn new(context: Rc<Context>, loc: NativeUniformLocation) -> Self {
-        const BLACK: Color = [0., 0., 0.];
+        const BLACK: Color = [0., 0., 0., 1.];
 
         Self {
             context,
@@ -33,8 +33,8 @@ impl Palette {
     pub fn set_uniform(&self) {
         unsafe {
             let colors = &self.colors;
-            let data = std::slice::from_raw_parts(colors.as_ptr().cast(), colors.len() * 3);
-            self.context.uniform_3_f32_slice(Some(&self.loc), data);
+            let data = std::slice::from_raw_parts(colors.as_ptr().cast(), colors.len() * 4);
+            self.context.uniform_4_f32_slice(Some(&self.loc), data);
         }
     }
 }
@@ -13,40 +13,72 @@ impl Program {
         layout (location = 1) in vec2 tex;
         layout (location = 2) in uint col;
         
-        uniform vec3 palette[16u];
+        uniform vec4 palette[16u];
+        uniform mat4 transform;
         
         out vec2 fs_tex;
-        out vec3 fs_col;
+        out vec4 fs_col;
         void main() {
             fs_tex = tex;
             if (col >= 16u) {
-                fs_col = vec3(1.0, 0.0, 0.0);
+                fs_col = vec4(1.0, 0.0, 0.0, 1.0);
             } else {
                 fs_col = palette[col];
             }
             
-            gl_Position = vec4(pos, 1.0);
+            gl_Position = transform * vec4(pos, 1.0);
         }"#;
 
     const FRAGMENT_SHADER: &'static str = r#"
         #version 330 core
         uniform usampler2D tex;
-        uniform vec3 palette[16u];
+        uniform vec4 palette[16u];
         uniform bool use_tex;
+        uniform bool linear_tex;
+        uniform sampler2D tex_palette;
+        uniform bool own_palette;
+        uniform bool transparent_tex;
         
         in vec2 fs_tex;
-        in vec3 fs_col;
+        in vec4 fs_col;
         out vec4 color;
+        
+        vec4 texel(vec2 uv) {
+            uint i = texture(tex, uv).r;
+            if (transparent_tex && i == 0u) {
+                return vec4(0.0);
+            }
+            
+            if (own_palette) {
+                return vec4(texelFetch(tex_palette, ivec2(int(i), 0), 0).rgb, 1.0);
+            }
+            
+            return palette[i];
+        }
+        
         void main() {
-            vec3 tex_col;
-            if (use_tex) {
-                uint i = texture(tex, fs_tex).r;
-                tex_col = palette[i];
+            vec4 tex_col;
+            if (use_tex && linear_tex) {
+                // Indices can't be interpolated, so the nearest texels are mixed after the lookup
+                vec2 size = vec2(textureSize(tex, 0));
+                vec2 st = fs_tex * size - 0.5;
+                vec2 base = floor(st);
+                vec2 f = st - base;
+                vec4 a = texel((base + vec2(0.5, 0.5)) / size);
+                vec4 b = texel((base + vec2(1.5, 0.5)) / size);
+                vec4 c = texel((base + vec2(0.5, 1.5)) / size);
+                vec4 d = texel((base + vec2(1.5, 1.5)) / size);
+                tex_col = mix(mix(a, b, f.x), mix(c, d, f.x), f.y);
+            } else if (use_tex) {
+                tex_col = texel(fs_tex);
             } else {
-                tex_col = vec3(1.0);
+                tex_col = vec4(1.0);
             }
             
-            color = vec4(tex_col * fs_col, 1.0);
+            color = tex_col * fs_col;
+            if (color.a <= 0.0) {
+                discard;
+            }
         }"#;
 
     pub fn new(context: Rc<Context>) -> Self {
@@ -97,10 +129,30 @@ impl Program {
         self.loc("palette")
     }
 
+    pub fn transform_loc(&self) -> NativeUniformLocation {
+        self.loc("transform")
+    }
+
     pub fn use_tex_loc(&self) -> NativeUniformLocation {
         self.loc("use_tex")
     }
 
+    pub fn linear_tex_loc(&self) -> NativeUniformLocation {
+        self.loc("linear_tex")
+    }
+
+    pub fn transparent_tex_loc(&self) -> NativeUniformLocation {
+        self.loc("transparent_tex")
+    }
+
+    pub fn tex_palette_loc(&self) -> NativeUniformLocation {
+        self.loc("tex_palette")
+    }
+
+    pub fn own_palette_loc(&self) -> NativeUniformLocation {
+        self.loc("own_palette")
+    }
+
     fn loc(&self, name: &str) -> NativeUniformLocation {
         unsafe {
             self.context
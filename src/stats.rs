@@ -0,0 +1,124 @@
+use gni::{stats::Counts, Usage};
+use std::{
+    io::{self, Read},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time accumulated on other threads.
+#[derive(Clone, Default)]
+pub struct Timer(Arc<AtomicU64>);
+
+impl Timer {
+    fn add(&self, time: Duration) {
+        self.0.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> Duration {
+        Duration::from_nanos(self.0.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// Times reading, which is mostly waiting for the producer.
+pub struct TimedRead<R> {
+    read: R,
+    timer: Timer,
+}
+
+impl<R> TimedRead<R> {
+    pub fn new(read: R, timer: Timer) -> Self {
+        Self { read, timer }
+    }
+}
+
+impl<R> Read for TimedRead<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let read = self.read.read(buf);
+        self.timer.add(start.elapsed());
+        read
+    }
+}
+
+/// Times decoding of commands, which includes reading.
+pub struct Timed<I> {
+    iter: I,
+    timer: Timer,
+}
+
+impl<I> Timed<I> {
+    pub fn new(iter: I, timer: Timer) -> Self {
+        Self { iter, timer }
+    }
+}
+
+impl<I> Iterator for Timed<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let next = self.iter.next();
+        self.timer.add(start.elapsed());
+        next
+    }
+}
+
+/// Prints a summary of every second to stderr, telling apart time spent waiting for the producer,
+/// parsing and executing commands, and the images held at the time.
+pub struct Report {
+    last: Instant,
+    read: Timer,
+    decode: Timer,
+    skipped: usize,
+}
+
+impl Report {
+    pub fn new(read: Timer, decode: Timer) -> Self {
+        Self {
+            last: Instant::now(),
+            read,
+            decode,
+            skipped: 0,
+        }
+    }
+
+    /// Counts a redraw without a new frame.
+    pub fn skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    /// Prints the summary if a second has passed since the last one.
+    pub fn tick<F>(&mut self, summary: F)
+    where
+        F: FnOnce() -> (Counts, Usage),
+    {
+        if self.last.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+
+        let read = self.read.take();
+        let decode = self.decode.take();
+        let (counts, usage) = summary();
+        eprintln!(
+            "{}, {:.2?} parsing, {:.2?} waiting for input, {} redraws without a new frame, \
+             {} held",
+            counts,
+            decode.saturating_sub(read),
+            read,
+            self.skipped,
+            usage,
+        );
+
+        self.last = Instant::now();
+        self.skipped = 0;
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fmt::Display,
+    io::{self, Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::{fs::FileTypeExt, net::UnixListener, net::UnixStream},
+    path::Path,
+};
+
+/// Maximum number of events waiting to be written.
+const EVENTS_LEN: usize = 256;
+
+/// Address to accept a client on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Listen {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            #[cfg(unix)]
+            Some(("unix", path)) if !path.is_empty() => Ok(Self::Unix(path.into())),
+            Some(("tcp", addr)) if !addr.is_empty() => Ok(Self::Tcp(addr.to_string())),
+            _ => Err(format!("invalid address {:?}", s)),
+        }
+    }
+}
+
+/// Connection of a client, commands are read from it and events are written back.
+pub struct Connection {
+    pub read: Box<dyn Read + Send>,
+    pub write: Box<dyn Write + Send>,
+}
+
+pub enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+impl Listener {
+    /// Binds the address. A unix socket file left by a crashed server is replaced.
+    pub fn bind(listen: &Listen) -> io::Result<Self> {
+        match listen {
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                remove_stale(path)?;
+                Ok(Self::Unix(UnixListener::bind(path)?, path.clone()))
+            }
+            Listen::Tcp(addr) => Ok(Self::Tcp(TcpListener::bind(addr.as_str())?)),
+        }
+    }
+
+    /// Waits for a single client. The socket file is removed once it is connected or failed to.
+    pub fn accept(self) -> io::Result<Connection> {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(listener, path) => {
+                let accepted = listener.accept();
+                let removed = std::fs::remove_file(path);
+                let (stream, _) = accepted?;
+                removed?;
+                Ok(Connection {
+                    read: Box::new(stream.try_clone()?),
+                    write: Box::new(stream),
+                })
+            }
+            Self::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Ok(Connection {
+                    read: Box::new(stream.try_clone()?),
+                    write: Box::new(stream),
+                })
+            }
+        }
+    }
+}
+
+/// Removes the socket file if no server listens on it.
+#[cfg(unix)]
+fn remove_stale(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() && UnixStream::connect(path).is_err() => {
+            std::fs::remove_file(path)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Writes events on a dedicated thread, so a client not reading them doesn't block the window.
+///
+/// Events are dropped while the queue is full. Once writing fails, no more events are sent.
+/// Queued events are written before it's dropped, waiting at most a second.
+pub struct Events {
+    send: Option<SyncSender<String>>,
+    /// Disconnected once the writing thread ends.
+    done: Receiver<()>,
+    dropping: bool,
+}
+
+impl Events {
+    pub fn spawn(mut write: Box<dyn Write + Send>) -> Self {
+        let (send, events) = mpsc::sync_channel::<String>(EVENTS_LEN);
+        let (done_send, done) = mpsc::channel();
+        thread::spawn(move || {
+            let _done = done_send;
+            for event in events {
+                if let Err(err) = write
+                    .write_all(event.as_bytes())
+                    .and_then(|_| write.flush())
+                {
+                    eprintln!("sending events failed, no more are sent: {}", err);
+                    return;
+                }
+            }
+        });
+
+        Self {
+            send: Some(send),
+            done,
+            dropping: false,
+        }
+    }
+
+    /// Queues the event without waiting for it to be written.
+    pub fn send<E>(&mut self, event: E)
+    where
+        E: Display,
+    {
+        let send = match &self.send {
+            Some(send) => send,
+            None => return,
+        };
+
+        match send.try_send(format!("{}\n", event)) {
+            Ok(()) => self.dropping = false,
+            Err(TrySendError::Full(_)) => {
+                if !self.dropping {
+                    eprintln!("the client doesn't read events, dropping them");
+                    self.dropping = true;
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => self.send = None,
+        }
+    }
+}
+
+impl Drop for Events {
+    fn drop(&mut self) {
+        self.send = None;
+        let _ = self.done.recv_timeout(Duration::from_secs(1));
+    }
+}
+
+impl std::fmt::Display for Listener {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            #[cfg(unix)]
+            Self::Unix(_, path) => write!(f, "unix:{}", path.display()),
+            Self::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "tcp:{}", addr),
+                Err(_) => write!(f, "tcp"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpStream, thread};
+
+    fn echo(listener: Listener) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut conn = listener.accept().unwrap();
+            let mut buf = [0; 4];
+            conn.read.read_exact(&mut buf).unwrap();
+            conn.write.write_all(&buf).unwrap();
+        })
+    }
+
+    #[test]
+    fn parse() {
+        let actual = "tcp:127.0.0.1:7000".parse();
+        let expected = Ok(Listen::Tcp("127.0.0.1:7000".to_string()));
+        assert_eq!(actual, expected);
+
+        #[cfg(unix)]
+        assert_eq!("unix:/tmp/gni".parse(), Ok(Listen::Unix("/tmp/gni".into())));
+
+        assert!("tcp:".parse::<Listen>().is_err());
+        assert!("udp:127.0.0.1:7000".parse::<Listen>().is_err());
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = Listener::bind(&Listen::Tcp("127.0.0.1:0".to_string())).unwrap();
+        let addr = match &listener {
+            Listener::Tcp(tcp) => tcp.local_addr().unwrap(),
+            #[cfg(unix)]
+            _ => unreachable!(),
+        };
+
+        let server = echo(listener);
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.write_all(b"c1\n\n").unwrap();
+        let mut buf = [0; 4];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"c1\n\n");
+        server.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix() {
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("gni-test-{}.sock", std::process::id()));
+        let listener = Listener::bind(&Listen::Unix(path.clone())).unwrap();
+        let server = echo(listener);
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"c1\n\n").unwrap();
+        let mut buf = [0; 4];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"c1\n\n");
+        server.join().unwrap();
+        assert!(!path.exists());
+
+        // The socket file of a listener that never accepted is stale
+        drop(Listener::bind(&Listen::Unix(path.clone())).unwrap());
+        assert!(path.exists());
+        let listener = Listener::bind(&Listen::Unix(path.clone())).unwrap();
+        assert!(Listener::bind(&Listen::Unix(path.clone())).is_err());
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn events() {
+        use std::sync::{Arc, Mutex};
+
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let mut events = Events::spawn(Box::new(Shared(out.clone())));
+        events.send("aa");
+        events.send("aq");
+        drop(events);
+        assert_eq!(*out.lock().unwrap(), b"aa\naq\n");
+
+        struct Closed;
+
+        impl Write for Closed {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // Nothing is sent after the writing failed
+        let mut events = Events::spawn(Box::new(Closed));
+        events.send("aa");
+        let _ = events.done.recv();
+        events.send("aq");
+        assert!(events.send.is_none());
+    }
+}
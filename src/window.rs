@@ -1,3 +1,5 @@
+use gni::input::{Action, Direction};
+
 type Context = glutin::ContextWrapper<glutin::PossiblyCurrent, glutin::window::Window>;
 type EventLoop = glutin::event_loop::EventLoop<()>;
 
@@ -19,6 +21,7 @@ impl Window {
         let context = unsafe {
             glutin::ContextBuilder::new()
                 .with_vsync(true)
+                .with_depth_buffer(24)
                 .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 3)))
                 .build_windowed(window_builder, &event_loop)
                 .unwrap()
@@ -36,37 +39,68 @@ impl Window {
         self.context.context()
     }
 
-    pub fn run<E>(self, mut ev: E, fps: u32) -> !
+    /// Runs the event loop until the event handler exits.
+    /// The handler is dropped before the context, so it can free GL resources.
+    pub fn run<E>(self, mut ev: E, fps: u32) -> i32
     where
-        E: crate::event::Event + 'static,
+        E: crate::event::Event,
     {
+        use crate::event::Frame;
         use glutin::{
             event::{Event, StartCause, WindowEvent},
             event_loop::ControlFlow,
+            platform::run_return::EventLoopExtRunReturn,
         };
         use std::time::{Duration, Instant};
 
         let micros = if fps == 0 { 0 } else { 1_000_000 / fps as u64 };
-        let context = self.context;
-        self.event_loop.run(move |event, _, flow| {
+        let Self {
+            context,
+            mut event_loop,
+        } = self;
+        let mut cursor = Cursor::new(context.window().inner_size().into());
+        let mut status = 0;
+        event_loop.run_return(|event, _, flow| {
             match event {
                 Event::WindowEvent { event, .. } => {
                     return match event {
                         WindowEvent::Resized(size) => {
                             context.resize(size);
+                            cursor.size = size.into();
                             ev.resize(size.into());
                         }
                         WindowEvent::CloseRequested => {
-                            *flow = ControlFlow::Exit;
+                            // Closing the window is the same as the quit action
+                            ev.action(Action::Quit);
+                        }
+                        WindowEvent::KeyboardInput { input, .. } => {
+                            if let Some(action) = key_action(input) {
+                                ev.action(action);
+                            }
+                        }
+                        WindowEvent::MouseInput { state, button, .. } => {
+                            if let Some(action) = cursor.button(state, button) {
+                                ev.action(action);
+                            }
+                        }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            if let Some(action) = cursor.moved(position.into()) {
+                                ev.action(action);
+                            }
                         }
                         _ => (),
-                    }
+                    };
                 }
                 Event::NewEvents(cause) => match cause {
-                    StartCause::ResumeTimeReached { .. } | StartCause::Poll => {
-                        ev.draw();
-                        context.swap_buffers().unwrap();
-                    }
+                    StartCause::ResumeTimeReached { .. } | StartCause::Poll => match ev.draw() {
+                        Frame::Skip => (),
+                        Frame::Present => context.swap_buffers().unwrap(),
+                        Frame::Exit(code) => {
+                            status = code;
+                            *flow = ControlFlow::Exit;
+                            return;
+                        }
+                    },
                     StartCause::WaitCancelled {
                         requested_resume, ..
                     } => {
@@ -84,6 +118,112 @@ impl Window {
             } else {
                 ControlFlow::WaitUntil(Instant::now() + Duration::from_micros(micros))
             };
-        })
+        });
+
+        drop(ev);
+        drop(context);
+        status
+    }
+}
+
+fn key_action(input: glutin::event::KeyboardInput) -> Option<Action> {
+    use glutin::event::{ElementState, VirtualKeyCode as Key};
+
+    if input.state != ElementState::Pressed {
+        return None;
+    }
+
+    let action = match input.virtual_keycode? {
+        Key::Left => Action::Direction(Direction::Left),
+        Key::Right => Action::Direction(Direction::Right),
+        Key::Up => Action::Direction(Direction::Up),
+        Key::Down => Action::Direction(Direction::Down),
+        Key::Q => Action::Left,
+        Key::E => Action::Right,
+        Key::Z => Action::A,
+        Key::X => Action::B,
+        Key::C => Action::C,
+        Key::V => Action::D,
+        Key::Return => Action::Start,
+        Key::Space => Action::Select,
+        Key::Escape => Action::Quit,
+        _ => return None,
+    };
+
+    Some(action)
+}
+
+/// Tracks the cursor to report clicks and drags in the clip space coordinates.
+struct Cursor {
+    size: (u32, u32),
+    pos: (f64, f64),
+    pressed: Option<glutin::event::MouseButton>,
+    last: Option<(i8, i8)>,
+}
+
+impl Cursor {
+    fn new(size: (u32, u32)) -> Self {
+        Self {
+            size,
+            pos: (0., 0.),
+            pressed: None,
+            last: None,
+        }
+    }
+
+    fn button(
+        &mut self,
+        state: glutin::event::ElementState,
+        button: glutin::event::MouseButton,
+    ) -> Option<Action> {
+        use glutin::event::{ElementState, MouseButton};
+
+        match (state, button) {
+            (ElementState::Pressed, MouseButton::Left | MouseButton::Right) => {
+                self.pressed = Some(button);
+                self.last = None;
+                self.action()
+            }
+            (ElementState::Released, _) if self.pressed == Some(button) => {
+                self.pressed = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn moved(&mut self, pos: (f64, f64)) -> Option<Action> {
+        self.pos = pos;
+        self.action()
+    }
+
+    fn action(&mut self) -> Option<Action> {
+        use glutin::event::MouseButton;
+
+        fn axis(pos: f64, len: u32) -> i8 {
+            if len == 0 {
+                return 0;
+            }
+
+            let clip = pos / len as f64 * 2. - 1.;
+            (clip * 128.).floor().clamp(i8::MIN as f64, i8::MAX as f64) as i8
+        }
+
+        let (width, height) = self.size;
+        let (x, y) = self.pos;
+        let pos = (axis(x, width), axis(height as f64 - y, height));
+        if self.last == Some(pos) {
+            return None;
+        }
+
+        let (x, y) = pos;
+        let action = match self.pressed? {
+            MouseButton::Left => Action::CursorLeft(x, y),
+            MouseButton::Right => Action::CursorRight(x, y),
+            _ => return None,
+        };
+
+        self.last = Some(pos);
+        Some(action)
     }
 }